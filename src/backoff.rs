@@ -0,0 +1,291 @@
+//! Classification of reconcile errors, and per-object exponential backoff.
+//!
+//! Conflicts are retried almost immediately, since they only mean that the
+//! object changed while it was being reconciled. Invalid and unadopted objects
+//! are retried at a fixed interval, since retrying does not help until the
+//! object or its parent zone changes. All other errors are retried with a
+//! delay which doubles with every consecutive failure of the same object,
+//! until it is reconciled successfully again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    hash::Hash,
+    sync::Mutex,
+    time::Duration,
+};
+
+use kube::{
+    runtime::{controller::Action, reflector::ObjectRef, watcher},
+    Resource,
+};
+use rand::Rng as _;
+use tracing::warn;
+
+use crate::error::Error;
+
+/// Kind of error encountered during a reconcile, which decides how soon it is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The object was modified concurrently (409).
+    Conflict,
+    /// The object, or one it refers to, does not exist (404).
+    NotFound,
+    /// The API server is rate limiting requests (429).
+    Throttled,
+    /// The API server failed to handle the request (5xx).
+    Server,
+    /// The API server rejected the request, for example due to missing permissions.
+    Rejected,
+    /// The API server could not be reached.
+    Network,
+    /// Any other error, such as failing to deserialize a response.
+    Other,
+    /// The object's spec is invalid.
+    Invalid,
+    /// The object was not adopted by any zone.
+    Unadopted,
+}
+
+impl ErrorClass {
+    pub fn of(error: &Error) -> Self {
+        match error {
+            Error::Api { source, .. } => ErrorClass::of_api(source),
+            Error::Invalid { .. } => ErrorClass::Invalid,
+            Error::Rejected { .. } => ErrorClass::Unadopted,
+        }
+    }
+
+    pub fn of_api(error: &kube::Error) -> Self {
+        match error {
+            kube::Error::Api(response) => match response.code {
+                409 => ErrorClass::Conflict,
+                404 => ErrorClass::NotFound,
+                429 => ErrorClass::Throttled,
+                500.. => ErrorClass::Server,
+                _ => ErrorClass::Rejected,
+            },
+            kube::Error::HyperError(_) | kube::Error::Service(_) | kube::Error::ReadEvents(_) => {
+                ErrorClass::Network
+            }
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+impl Display for ErrorClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorClass::Conflict => "conflict",
+            ErrorClass::NotFound => "not found",
+            ErrorClass::Throttled => "throttled",
+            ErrorClass::Server => "server error",
+            ErrorClass::Rejected => "rejected",
+            ErrorClass::Network => "network error",
+            ErrorClass::Other => "other",
+            ErrorClass::Invalid => "invalid",
+            ErrorClass::Unadopted => "unadopted",
+        })
+    }
+}
+
+/// Delays between retries of failed reconciles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackoffPolicy {
+    /// Delay before retrying after a conflict, regardless of previous failures.
+    pub conflict_delay: Duration,
+
+    /// Delay after the first failure, doubled with every consecutive failure.
+    pub initial_backoff: Duration,
+
+    /// Lower limit for the delay while the API server is throttling requests.
+    pub throttled_backoff: Duration,
+
+    /// Upper limit for the delay between retries.
+    pub max_backoff: Duration,
+
+    /// Delay before retrying invalid or unadopted objects, regardless of previous failures.
+    pub rejected_delay: Duration,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            conflict_delay: Duration::from_secs(1),
+            initial_backoff: Duration::from_secs(1),
+            throttled_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(300),
+            rejected_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// Delay before retrying an error of the given `class`, after
+    /// `failures` consecutive failures of the same object, before jitter.
+    pub fn backoff(&self, class: ErrorClass, failures: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(1 << failures.saturating_sub(1).min(31))
+            .min(self.max_backoff);
+
+        match class {
+            ErrorClass::Conflict => self.conflict_delay,
+            ErrorClass::Invalid | ErrorClass::Unadopted => self.rejected_delay,
+            ErrorClass::Throttled => exponential.max(self.throttled_backoff),
+            _ => exponential,
+        }
+    }
+}
+
+/// Randomize `delay` to between half and all of its length, so objects
+/// which failed at the same time are not all retried at the same time.
+pub fn jitter(delay: Duration) -> Duration {
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Consecutive failures of each object reconciled by a single controller.
+pub struct ErrorBackoff<K: Resource> {
+    pub policy: BackoffPolicy,
+    failures: Mutex<HashMap<ObjectRef<K>, u32>>,
+
+    /// Last rejection reported for each invalid or unadopted object, so that
+    /// it is only reported again once the reason changes.
+    rejections: Mutex<HashMap<ObjectRef<K>, String>>,
+
+    /// Objects seen so far while the controller's watch is relisting, if it is.
+    relisted: Mutex<Option<HashSet<ObjectRef<K>>>>,
+}
+
+impl<K: Resource> ErrorBackoff<K>
+where
+    K::DynamicType: Eq + Hash,
+{
+    pub fn new(policy: BackoffPolicy) -> Self {
+        ErrorBackoff {
+            policy,
+            failures: Mutex::new(HashMap::new()),
+            rejections: Mutex::new(HashMap::new()),
+            relisted: Mutex::new(None),
+        }
+    }
+}
+
+impl<K: Resource> Default for ErrorBackoff<K>
+where
+    K::DynamicType: Eq + Hash,
+{
+    fn default() -> Self {
+        ErrorBackoff::new(BackoffPolicy::default())
+    }
+}
+
+impl<K> ErrorBackoff<K>
+where
+    K: Resource,
+    K::DynamicType: Default + Eq + Hash + Clone + Debug,
+{
+    /// Record the failed reconcile of `object`, and decide when to retry it.
+    pub fn on_error(&self, object: &K, error: &Error) -> Action {
+        let class = ErrorClass::of(error);
+        let object = ObjectRef::from_obj(object);
+
+        // Conflicts are resolved by simply trying again, and invalid or unadopted
+        // objects are not expected to be resolved by retrying at all, so neither
+        // count towards the object's consecutive failures.
+        let failures = if matches!(
+            class,
+            ErrorClass::Conflict | ErrorClass::Invalid | ErrorClass::Unadopted
+        ) {
+            self.failures(&object)
+        } else {
+            let mut failures = self.failures.lock().unwrap();
+            let count = failures.entry(object.clone()).or_default();
+            *count = count.saturating_add(1);
+            *count
+        };
+
+        let delay = jitter(self.policy.backoff(class, failures));
+
+        warn!(
+            %class,
+            reason = error.reason(),
+            failures,
+            backoff_secs = delay.as_secs_f64(),
+            "{error}, retrying in {delay:.1?}",
+        );
+
+        Action::requeue(delay)
+    }
+
+    /// Forget the failures and rejections of objects which were deleted, as observed
+    /// through an `event` of the controller's watch, since they are never reset otherwise.
+    ///
+    /// Objects deleted while the watch was restarting are forgotten once the
+    /// relist completes without them.
+    pub fn observe(&self, event: &watcher::Event<K>) {
+        let mut relisted = self.relisted.lock().unwrap();
+
+        match event {
+            watcher::Event::Delete(object) => {
+                let object = ObjectRef::from_obj(object);
+                self.reset(&object);
+                self.accepted(&object);
+            }
+            watcher::Event::Init => *relisted = Some(HashSet::new()),
+            watcher::Event::InitApply(object) => {
+                if let Some(relisted) = relisted.as_mut() {
+                    relisted.insert(ObjectRef::from_obj(object));
+                }
+            }
+            watcher::Event::InitDone => {
+                if let Some(relisted) = relisted.take() {
+                    self.failures
+                        .lock()
+                        .unwrap()
+                        .retain(|object, _| relisted.contains(object));
+                    self.rejections
+                        .lock()
+                        .unwrap()
+                        .retain(|object, _| relisted.contains(object));
+                }
+            }
+            watcher::Event::Apply(_) => (),
+        }
+    }
+
+    /// Reset the failure count of `object`, after it was reconciled successfully.
+    pub fn reset(&self, object: &ObjectRef<K>) {
+        self.failures.lock().unwrap().remove(object);
+    }
+
+    /// Check whether `rejection` is the last one reported for `object`.
+    pub fn is_reported(&self, object: &ObjectRef<K>, rejection: &str) -> bool {
+        self.rejections
+            .lock()
+            .unwrap()
+            .get(object)
+            .is_some_and(|reported| reported == rejection)
+    }
+
+    /// Remember that `rejection` was reported for `object`.
+    pub fn mark_reported(&self, object: ObjectRef<K>, rejection: String) {
+        self.rejections.lock().unwrap().insert(object, rejection);
+    }
+
+    /// Forget the rejection reported for `object`, after it was reconciled
+    /// without being rejected, so a later rejection is reported again.
+    pub fn accepted(&self, object: &ObjectRef<K>) {
+        self.rejections.lock().unwrap().remove(object);
+    }
+
+    /// Number of consecutive failures of `object`.
+    pub fn failures(&self, object: &ObjectRef<K>) -> u32 {
+        self.failures
+            .lock()
+            .unwrap()
+            .get(object)
+            .copied()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,238 @@
+//! Offline resolution of Zone and Record manifests.
+//!
+//! Loads Zones and Records from YAML files and runs the same parent resolution,
+//! delegation validation and entry building as the controllers would, without
+//! requiring access to a cluster.
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use kube::Resource;
+use kubizone_common::FullyQualifiedDomainName;
+use kubizone_crds::{
+    v1alpha1::{DomainExt as _, Record, Zone, ZoneEntry, ZoneRef},
+    PARENT_ZONE_LABEL,
+};
+use serde::Deserialize;
+
+use crate::resolution::{Rejection, Snapshot, SoaDefaults, TtlPolicy};
+
+/// Namespace assumed for manifests which do not specify one.
+const DEFAULT_NAMESPACE: &str = "default";
+
+/// Zones and Records loaded from manifests.
+#[derive(Debug, Default)]
+pub struct Manifests {
+    pub zones: Vec<Zone>,
+    pub records: Vec<Record>,
+}
+
+/// Error encountered while loading manifests.
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl Manifests {
+    /// Load all Zones and Records from the YAML files within `path`, which
+    /// is either a single file or a directory searched recursively.
+    ///
+    /// Documents describing other kinds of resources are ignored.
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let mut manifests = Manifests::default();
+
+        for file in yaml_files(path)? {
+            let contents = std::fs::read_to_string(&file).map_err(|err| LoadError {
+                path: file.clone(),
+                message: err.to_string(),
+            })?;
+
+            manifests.parse(&contents).map_err(|message| LoadError {
+                path: file,
+                message,
+            })?;
+        }
+
+        Ok(manifests)
+    }
+
+    /// Parse the Zones and Records from a multi-document YAML stream.
+    pub fn parse(&mut self, contents: &str) -> Result<(), String> {
+        for document in serde_yaml::Deserializer::from_str(contents) {
+            let value = serde_yaml::Value::deserialize(document).map_err(|err| err.to_string())?;
+            if value.is_null() {
+                continue;
+            }
+
+            let api_version = value.get("apiVersion").and_then(|value| value.as_str());
+            let kind = value.get("kind").and_then(|value| value.as_str());
+
+            if api_version == Some(&Zone::api_version(&())) && kind == Some(&Zone::kind(&())) {
+                let mut zone: Zone =
+                    serde_yaml::from_value(value).map_err(|err| err.to_string())?;
+                zone.status = None;
+                self.zones.push(prepare(zone));
+            } else if api_version == Some(&Record::api_version(&()))
+                && kind == Some(&Record::kind(&()))
+            {
+                let mut record: Record =
+                    serde_yaml::from_value(value).map_err(|err| err.to_string())?;
+                record.status = None;
+                self.records.push(prepare(record));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Strip the parent zone label previously set by a controller from the manifest,
+/// and fill in the metadata the API server would otherwise provide.
+///
+/// The status of exported manifests is cleared by the caller, since it is
+/// specific to Zones and Records.
+fn prepare<K: Resource>(mut resource: K) -> K {
+    let meta = resource.meta_mut();
+
+    let namespace = meta
+        .namespace
+        .get_or_insert_with(|| DEFAULT_NAMESPACE.to_string())
+        .clone();
+
+    // Zones compare uids to make sure they do not adopt themselves.
+    meta.uid
+        .get_or_insert_with(|| format!("{namespace}/{}", meta.name.as_deref().unwrap_or_default()));
+
+    if let Some(labels) = meta.labels.as_mut() {
+        labels.remove(PARENT_ZONE_LABEL);
+    }
+
+    resource
+}
+
+fn yaml_files(path: &Path) -> Result<Vec<PathBuf>, LoadError> {
+    let error = |err: std::io::Error| LoadError {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    };
+
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut entries = std::fs::read_dir(path)
+        .map_err(error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(yaml_files(&entry)?);
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == "yaml" || extension == "yml")
+        {
+            files.push(entry);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Resolved state of a single Zone.
+#[derive(Debug)]
+pub struct ZoneReport {
+    pub zone: String,
+    pub fqdn: Option<FullyQualifiedDomainName>,
+    pub parent: Option<ZoneRef>,
+    pub entries: Vec<ZoneEntry>,
+}
+
+/// Outcome of resolving a set of manifests.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub zones: Vec<ZoneReport>,
+    pub rejections: Vec<Rejection>,
+}
+
+impl Report {
+    /// Check whether any Records were rejected.
+    pub fn has_rejected_records(&self) -> bool {
+        self.rejections
+            .iter()
+            .any(|rejection| rejection.kind == Record::kind(&()))
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for zone in &self.zones {
+            write!(f, "zone {}", zone.zone)?;
+            if let Some(fqdn) = &zone.fqdn {
+                write!(f, " ({fqdn})")?;
+            }
+            if let Some(parent) = &zone.parent {
+                write!(f, " parent {}", parent.as_label())?;
+            }
+            writeln!(f)?;
+
+            for entry in &zone.entries {
+                writeln!(
+                    f,
+                    "    {} {} {} {} {}",
+                    entry.fqdn, entry.ttl, entry.class, entry.type_, entry.rdata
+                )?;
+            }
+        }
+
+        for rejection in &self.rejections {
+            writeln!(f, "rejected {rejection}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolve the fqdns, parents and entries of all manifests, the same way the
+/// controllers would once the manifests have been applied to a cluster.
+///
+/// The records of an RRset are published with the TTL chosen by `ttl_policy`,
+/// and the SOA record of every zone is filled in from `soa`.
+pub fn check(manifests: Manifests, ttl_policy: TtlPolicy, soa: SoaDefaults) -> Report {
+    let mut snapshot = Snapshot {
+        zones: manifests.zones,
+        records: manifests.records,
+        ttl_policy,
+        soa,
+    };
+
+    let rejections = snapshot.resolve(time::OffsetDateTime::now_utc());
+
+    Report {
+        zones: snapshot
+            .zones
+            .into_iter()
+            .map(|zone| ZoneReport {
+                zone: zone.to_string(),
+                fqdn: zone.fqdn().cloned(),
+                parent: zone.parent(),
+                entries: zone.status.map(|status| status.entries).unwrap_or_default(),
+            })
+            .collect(),
+        rejections,
+    }
+}
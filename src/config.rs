@@ -0,0 +1,231 @@
+//! Configuration file for the reconcile command.
+//!
+//! The file can be written in either YAML or TOML, and covers the same
+//! settings as the command line flags. Flags and environment variables
+//! always take precedence over values read from the file.
+//!
+//! Zone entries are only ever published into the status of each Zone, where
+//! other tools pick them up, so there are no output sinks to configure.
+//!
+//! ```yaml
+//! requeueTimeSecs: 30
+//! workerThreads: 4
+//! maxRestarts: 5
+//! dryRun: false
+//! rrsetTtl: minimum
+//! soa:
+//!   nameserver: ns1.example.org.
+//!   mailbox: hostmaster.example.org.
+//! controllers:
+//!   zone: true
+//!   record: true
+//!   ingress: true
+//! concurrency:
+//!   zone: 4
+//!   record: 16
+//! scope:
+//!   namespaces: [dns]
+//!   zoneSelector: shard=a
+//!   recordSelector: shard=a
+//!   shard: a
+//! ingress:
+//!   classes: [nginx]
+//!   namespaces: [default]
+//!   selector: dns=public
+//! ```
+
+use std::{
+    fmt::Display,
+    num::{NonZeroU16, NonZeroU64, NonZeroUsize},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::resolution::{SoaDefaults, TtlPolicy};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Config {
+    /// Default time to wait between requeuing resources.
+    pub requeue_time_secs: Option<NonZeroU64>,
+
+    /// Number of threads to run the controllers on. Runs everything
+    /// on a single thread if unset.
+    pub worker_threads: Option<NonZeroUsize>,
+
+    /// Number of consecutive restarts after which a failing
+    /// controller is considered unrecoverable.
+    pub max_restarts: Option<u32>,
+
+    /// Only log the patches the controllers would send, and validate
+    /// them using server-side dry-run, without persisting any changes.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// TTL to publish all records of an RRset with, if they differ.
+    pub rrset_ttl: Option<TtlPolicy>,
+
+    #[serde(default)]
+    pub soa: SoaDefaults,
+
+    #[serde(default)]
+    pub controllers: ControllersConfig,
+
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+
+    #[serde(default)]
+    pub scope: ScopeConfig,
+
+    #[serde(default)]
+    pub ingress: IngressConfig,
+}
+
+/// Enablement of the individual controllers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ControllersConfig {
+    #[serde(default = "enabled")]
+    pub zone: bool,
+
+    #[serde(default = "enabled")]
+    pub record: bool,
+
+    /// Create Records for all ingresses based on their hosts and
+    /// loadBalancer settings.
+    pub ingress: Option<bool>,
+}
+
+impl Default for ControllersConfig {
+    fn default() -> Self {
+        ControllersConfig {
+            zone: true,
+            record: true,
+            ingress: None,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Maximum number of concurrent reconciles per controller. Unlimited if unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    pub zone: Option<NonZeroU16>,
+    pub record: Option<NonZeroU16>,
+    pub ingress: Option<NonZeroU16>,
+}
+
+/// Namespaces and selectors the controllers operate within. See [`crate::Scope`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScopeConfig {
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    pub zone_selector: Option<String>,
+    pub record_selector: Option<String>,
+    pub shard: Option<String>,
+}
+
+/// Filters for the ingress record source. See [`crate::ingress::IngressFilter`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IngressConfig {
+    #[serde(default)]
+    pub classes: Vec<String>,
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    pub selector: Option<String>,
+}
+
+/// Format of a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Determine the format from the file extension, defaulting to YAML.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+/// Error encountered while reading or parsing a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// Line and column (both starting at 1) the error occurred at, if known.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "line {line}, column {column}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Read and parse the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| ConfigError {
+            location: None,
+            message: format!("failed to read {}: {err}", path.display()),
+        })?;
+
+        Config::parse(&contents, Format::of(path))
+    }
+
+    /// Parse configuration from `contents` in the given format.
+    pub fn parse(contents: &str, format: Format) -> Result<Self, ConfigError> {
+        match format {
+            Format::Yaml => {
+                // An empty file is a valid, if pointless, configuration.
+                if contents.trim().is_empty() {
+                    return Ok(Config::default());
+                }
+
+                serde_yaml::from_str(contents).map_err(|err| {
+                    let location = err
+                        .location()
+                        .map(|location| (location.line(), location.column()));
+
+                    // serde_yaml appends the location to the message itself.
+                    let message = err.to_string();
+                    let message = match message.rfind(" at line ") {
+                        Some(index) if location.is_some() => message[..index].to_string(),
+                        _ => message,
+                    };
+
+                    ConfigError { location, message }
+                })
+            }
+            Format::Toml => toml::from_str(contents).map_err(|err| ConfigError {
+                location: err.span().map(|span| line_column(contents, span.start)),
+                message: err.message().to_string(),
+            }),
+        }
+    }
+}
+
+/// Convert a byte offset within `contents` into a line and column, both starting at 1.
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+
+    (line, column)
+}
@@ -0,0 +1,172 @@
+//! Rendering, installation and verification of the Zone and Record
+//! CustomResourceDefinitions for the API version this binary was built for.
+
+use std::time::Duration;
+
+use k8s_openapi::{
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+        CustomResourceDefinition, CustomResourceDefinitionVersion,
+    },
+    serde_json::Value,
+};
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{conditions, wait::await_condition},
+    Api, Client, CustomResourceExt,
+};
+use kubizone_crds::v1alpha1::{Record, Zone};
+use tracing::*;
+
+#[cfg(feature = "dev")]
+const CONTROLLER_NAME: &str = "dev.kubi.zone/crd-installer";
+#[cfg(not(feature = "dev"))]
+const CONTROLLER_NAME: &str = "kubi.zone/crd-installer";
+
+/// Maximum time to wait for installed CRDs to become established.
+const ESTABLISH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// CustomResourceDefinitions expected by this binary.
+pub fn definitions() -> Vec<CustomResourceDefinition> {
+    vec![Zone::crd(), Record::crd()]
+}
+
+/// Render all definitions as a multi-document YAML stream.
+pub fn render() -> Result<String, serde_yaml::Error> {
+    definitions()
+        .iter()
+        .map(|crd| Ok(format!("---\n{}", serde_yaml::to_string(crd)?)))
+        .collect()
+}
+
+/// Install or update the definitions using server-side apply, and wait for
+/// them to become established. Existing resources are left untouched.
+pub async fn install(client: Client) -> Result<(), kube::Error> {
+    let api = Api::<CustomResourceDefinition>::all(client);
+
+    for crd in definitions() {
+        let name = crd.metadata.name.clone().unwrap_or_default();
+
+        info!("applying CustomResourceDefinition {name}");
+        api.patch(
+            &name,
+            &PatchParams::apply(CONTROLLER_NAME),
+            &Patch::Apply(&crd),
+        )
+        .await?;
+
+        match tokio::time::timeout(
+            ESTABLISH_TIMEOUT,
+            await_condition(api.clone(), &name, conditions::is_crd_established()),
+        )
+        .await
+        {
+            Ok(Ok(_)) => info!("CustomResourceDefinition {name} established"),
+            Ok(Err(err)) => warn!("failed to wait for {name} to become established: {err}"),
+            Err(_) => warn!("timed out waiting for {name} to become established"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the installed definitions against the expected ones, returning
+/// a description of every difference found.
+pub async fn check(client: Client) -> Result<Vec<String>, kube::Error> {
+    let api = Api::<CustomResourceDefinition>::all(client);
+
+    let mut differences = Vec::new();
+    for expected in definitions() {
+        let name = expected.metadata.name.clone().unwrap_or_default();
+
+        match api.get_opt(&name).await? {
+            Some(installed) => differences.extend(compare(&expected, &installed)),
+            None => differences.push(format!("{name}: not installed")),
+        }
+    }
+
+    Ok(differences)
+}
+
+/// Describe how the `installed` definition differs from the `expected` one.
+///
+/// Only the parts of the definition the controller depends on are compared,
+/// since the API server fills in defaults for the remaining fields.
+pub fn compare(
+    expected: &CustomResourceDefinition,
+    installed: &CustomResourceDefinition,
+) -> Vec<String> {
+    let name = expected.metadata.name.as_deref().unwrap_or_default();
+    let mut differences = Vec::new();
+
+    if expected.spec.group != installed.spec.group {
+        differences.push(format!(
+            "{name}: group is {}, expected {}",
+            installed.spec.group, expected.spec.group
+        ));
+    }
+
+    if expected.spec.scope != installed.spec.scope {
+        differences.push(format!(
+            "{name}: scope is {}, expected {}",
+            installed.spec.scope, expected.spec.scope
+        ));
+    }
+
+    if expected.spec.names.kind != installed.spec.names.kind
+        || expected.spec.names.plural != installed.spec.names.plural
+    {
+        differences.push(format!(
+            "{name}: names are {}/{}, expected {}/{}",
+            installed.spec.names.kind,
+            installed.spec.names.plural,
+            expected.spec.names.kind,
+            expected.spec.names.plural
+        ));
+    }
+
+    for version in &expected.spec.versions {
+        let Some(installed_version) = installed
+            .spec
+            .versions
+            .iter()
+            .find(|installed_version| installed_version.name == version.name)
+        else {
+            differences.push(format!("{name}: version {} is missing", version.name));
+            continue;
+        };
+
+        if version.served && !installed_version.served {
+            differences.push(format!("{name}: version {} is not served", version.name));
+        }
+
+        if version.storage != installed_version.storage {
+            differences.push(format!(
+                "{name}: version {} is {}the storage version",
+                version.name,
+                if installed_version.storage {
+                    ""
+                } else {
+                    "not "
+                }
+            ));
+        }
+
+        if schema(version) != schema(installed_version) {
+            differences.push(format!(
+                "{name}: schema of version {} does not match",
+                version.name
+            ));
+        }
+    }
+
+    differences
+}
+
+/// OpenAPI schema of the given version, in a comparable form.
+fn schema(version: &CustomResourceDefinitionVersion) -> Option<Value> {
+    version
+        .schema
+        .as_ref()
+        .and_then(|schema| schema.open_api_v3_schema.as_ref())
+        .and_then(|schema| k8s_openapi::serde_json::to_value(schema).ok())
+}
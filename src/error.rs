@@ -0,0 +1,170 @@
+//! Errors encountered while reconciling Zones, Records and Ingresses.
+
+use std::fmt::Display;
+
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::Resource;
+
+/// Step of a reconcile during which an API request failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Fetching the candidate parent zones.
+    FetchZones,
+    /// Listing the records adopted by, or claimed by another owner.
+    ListRecords,
+    /// Setting `status.fqdn`.
+    SetFqdn,
+    /// Setting or removing the parent zone label.
+    SetParent,
+    /// Writing the zone's entries, hash and serial, or the ingress' records annotation.
+    UpdateStatus,
+    /// Annotating a record with the zone serial its content was published at.
+    MarkPublished,
+    /// Annotating a record with the TTL its RRset is published with.
+    FlagTtl,
+    /// Removing the publication annotation from a record which is no longer published.
+    WithdrawPublication,
+    /// Publishing Events about conflicting records.
+    ReportConflict,
+    /// Publishing an Event about why an object was not adopted, or is invalid.
+    ReportRejection,
+    /// Applying a record generated from an ingress.
+    ApplyRecord,
+    /// Deleting a stale record generated from an ingress.
+    DeleteRecord,
+    /// Adding the finalizer to a zone.
+    AddFinalizer,
+    /// Removing the finalizer from a zone, once its children have been detached.
+    RemoveFinalizer,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Step::FetchZones => "fetching zones",
+            Step::ListRecords => "listing records",
+            Step::SetFqdn => "setting fqdn",
+            Step::SetParent => "setting parent zone",
+            Step::UpdateStatus => "updating status",
+            Step::MarkPublished => "marking record published",
+            Step::FlagTtl => "flagging normalized ttl",
+            Step::WithdrawPublication => "withdrawing publication",
+            Step::ReportConflict => "reporting conflict",
+            Step::ReportRejection => "reporting rejection",
+            Step::ApplyRecord => "applying record",
+            Step::DeleteRecord => "deleting record",
+            Step::AddFinalizer => "adding finalizer",
+            Step::RemoveFinalizer => "removing finalizer",
+        })
+    }
+}
+
+/// Reason a reconcile failed.
+#[derive(Debug)]
+pub enum Error {
+    /// A request to the API server failed.
+    Api {
+        object: ObjectReference,
+        step: Step,
+        source: kube::Error,
+    },
+    /// The object's spec is invalid, and cannot be reconciled until it is changed.
+    Invalid {
+        object: ObjectReference,
+        reason: String,
+    },
+    /// The object was not adopted, because its parent zone does not
+    /// exist, is out of scope, or its delegations do not allow it.
+    Rejected {
+        object: ObjectReference,
+        reason: String,
+    },
+}
+
+impl Error {
+    /// Failed API request made for `resource` during `step`.
+    pub fn api<K>(resource: &K, step: Step, source: kube::Error) -> Self
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        Error::Api {
+            object: resource.object_ref(&()),
+            step,
+            source,
+        }
+    }
+
+    pub fn invalid<K>(resource: &K, reason: impl Into<String>) -> Self
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        Error::Invalid {
+            object: resource.object_ref(&()),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn rejected<K>(resource: &K, reason: impl Into<String>) -> Self
+    where
+        K: Resource<DynamicType = ()>,
+    {
+        Error::Rejected {
+            object: resource.object_ref(&()),
+            reason: reason.into(),
+        }
+    }
+
+    /// Object the error occurred for.
+    pub fn object(&self) -> &ObjectReference {
+        match self {
+            Error::Api { object, .. }
+            | Error::Invalid { object, .. }
+            | Error::Rejected { object, .. } => object,
+        }
+    }
+
+    /// Reason the object was rejected, if it is invalid or was not adopted.
+    pub fn rejection(&self) -> Option<&str> {
+        match self {
+            Error::Invalid { reason, .. } | Error::Rejected { reason, .. } => Some(reason),
+            Error::Api { .. } => None,
+        }
+    }
+
+    /// Short machine readable reason, in the style of a status condition reason.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::Api { .. } => "ApiError",
+            Error::Invalid { .. } => "InvalidSpec",
+            Error::Rejected { .. } => "NotAdopted",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let object = self.object();
+        write!(
+            f,
+            "{} {}/{}",
+            object.kind.as_deref().unwrap_or_default(),
+            object.namespace.as_deref().unwrap_or_default(),
+            object.name.as_deref().unwrap_or_default()
+        )?;
+
+        match self {
+            Error::Api { step, source, .. } => write!(f, ": {step} failed: {source}"),
+            Error::Invalid { reason, .. } => write!(f, " is invalid: {reason}"),
+            Error::Rejected { reason, .. } => write!(f, " was not adopted: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Api { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
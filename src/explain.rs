@@ -0,0 +1,452 @@
+//! Explanations of why a Zone or Record is, or is not, adopted.
+//!
+//! Lists every candidate parent zone the longest-match logic considers for an
+//! object, and evaluates each of their delegations against it, rule by rule.
+
+use std::fmt::Display;
+
+use kube::{Api, Client, Resource, ResourceExt};
+use kubizone_common::{DomainName, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::{Delegation, DomainExt, Record, RecordDelegation, Zone, ZoneRef};
+
+use crate::resolution::{
+    allowed_owner, candidate_zones, referenced_zone, resolve_record, resolve_zone, Resolution,
+};
+
+/// Outcome of evaluating a single delegation rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleCheck {
+    pub rule: String,
+    pub allowed: bool,
+    pub reason: String,
+}
+
+/// Outcome of evaluating one of a candidate zone's delegations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegationCheck {
+    pub index: usize,
+    pub namespace: RuleCheck,
+    pub rules: Vec<RuleCheck>,
+}
+
+impl DelegationCheck {
+    /// Check whether this delegation allows the adoption.
+    pub fn allows(&self) -> bool {
+        self.namespace.allowed && self.rules.iter().any(|rule| rule.allowed)
+    }
+}
+
+/// Why a zone was considered as a parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Role {
+    /// Referenced through the object's zoneRef.
+    Referenced,
+    /// Most immediate parent zone, whose delegations decide the adoption.
+    MostImmediate,
+    /// Parent zone shadowed by a more specific zone.
+    Shadowed { by: String },
+}
+
+/// Candidate parent zone along with the evaluation of its delegations.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub zone: String,
+    pub fqdn: Option<FullyQualifiedDomainName>,
+    pub role: Role,
+    pub delegations: Vec<DelegationCheck>,
+}
+
+impl Candidate {
+    /// Check whether any of the candidate's delegations allow the adoption.
+    pub fn allows(&self) -> bool {
+        self.delegations.iter().any(DelegationCheck::allows)
+    }
+}
+
+/// Explanation of the adoption of a single Zone or Record.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub kind: String,
+    pub object: String,
+    pub fqdn: Option<FullyQualifiedDomainName>,
+    pub current_parent: Option<ZoneRef>,
+    pub owner: Option<RuleCheck>,
+    pub candidates: Vec<Candidate>,
+    pub resolution: Resolution,
+}
+
+/// What is being adopted, which decides the delegation rules that apply.
+enum Subject {
+    Record(Type),
+    Zone,
+}
+
+/// Explain the adoption of `record`, given all `zones` in the cluster.
+///
+/// Like the controllers, zones which are being deleted are not considered.
+pub fn explain_record(record: &Record, zones: &[Zone]) -> Explanation {
+    let zones = candidate_zones(zones.iter().cloned());
+    let subject = Subject::Record(record.spec.type_);
+    let mut explanation = explain(
+        record,
+        record.spec.zone_ref.as_ref(),
+        &record.spec.domain_name,
+        &subject,
+        &zones,
+        resolve_record(record, &zones),
+    );
+
+    explanation.owner = explanation.fqdn.as_ref().map(|fqdn| {
+        let type_ = record.spec.type_;
+        let allowed = allowed_owner(fqdn, type_);
+
+        RuleCheck {
+            rule: format!("owner name {fqdn}"),
+            allowed,
+            reason: if allowed {
+                format!("{type_} records may be owned by {fqdn}")
+            } else {
+                format!("{type_} records are never allowed at wildcard owner names")
+            },
+        }
+    });
+    explanation
+}
+
+/// Explain the adoption of `zone`, given all `zones` in the cluster.
+///
+/// Like the controllers, zones which are being deleted are not considered.
+pub fn explain_zone(zone: &Zone, zones: &[Zone]) -> Explanation {
+    let zones = candidate_zones(zones.iter().cloned());
+    explain(
+        zone,
+        zone.spec.zone_ref.as_ref(),
+        &zone.spec.domain_name,
+        &Subject::Zone,
+        &zones,
+        resolve_zone(zone, &zones),
+    )
+}
+
+fn explain<K>(
+    resource: &K,
+    zone_ref: Option<&ZoneRef>,
+    domain_name: &DomainName,
+    subject: &Subject,
+    zones: &[Zone],
+    resolution: Resolution,
+) -> Explanation
+where
+    K: Resource<DynamicType = ()> + DomainExt + Display,
+{
+    let namespace = resource.namespace().unwrap_or_default();
+
+    let mut explanation = Explanation {
+        kind: K::kind(&()).to_string(),
+        object: resource.to_string(),
+        fqdn: None,
+        current_parent: resource.parent(),
+        owner: None,
+        candidates: Vec::new(),
+        resolution,
+    };
+
+    match (zone_ref, domain_name) {
+        (Some(zone_ref), DomainName::Partial(partial_domain)) => {
+            let Some(parent) = referenced_zone(resource, zone_ref, zones) else {
+                return explanation;
+            };
+
+            let fqdn = parent
+                .fqdn()
+                .map(|parent_fqdn| partial_domain.with_origin(parent_fqdn));
+
+            explanation.candidates.push(candidate(
+                parent,
+                Role::Referenced,
+                &namespace,
+                fqdn.as_ref(),
+                subject,
+            ));
+            explanation.fqdn = fqdn;
+        }
+        (None, DomainName::Full(fqdn)) => {
+            let mut parents: Vec<&Zone> = zones
+                .iter()
+                .filter(|zone| {
+                    zone.fqdn()
+                        .is_some_and(|parent_fqdn| fqdn.is_subdomain_of(parent_fqdn))
+                })
+                .collect();
+
+            // Longest, and therefore most immediate, parent first.
+            parents.sort_by_key(|zone| std::cmp::Reverse(zone.fqdn().unwrap().len()));
+
+            for (index, parent) in parents.iter().enumerate() {
+                let role = if index == 0 {
+                    Role::MostImmediate
+                } else {
+                    Role::Shadowed {
+                        by: parents[0].to_string(),
+                    }
+                };
+
+                explanation.candidates.push(candidate(
+                    parent,
+                    role,
+                    &namespace,
+                    Some(fqdn),
+                    subject,
+                ));
+            }
+
+            explanation.fqdn = Some(fqdn.clone());
+        }
+        _ => (),
+    }
+
+    explanation
+}
+
+fn candidate(
+    parent: &Zone,
+    role: Role,
+    namespace: &str,
+    fqdn: Option<&FullyQualifiedDomainName>,
+    subject: &Subject,
+) -> Candidate {
+    let delegations = match (parent.fqdn(), fqdn) {
+        (Some(parent_fqdn), Some(fqdn)) => parent
+            .spec
+            .delegations
+            .iter()
+            .enumerate()
+            .map(|(index, delegation)| {
+                check_delegation(index, delegation, namespace, parent_fqdn, fqdn, subject)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Candidate {
+        zone: parent.to_string(),
+        fqdn: parent.fqdn().cloned(),
+        role,
+        delegations,
+    }
+}
+
+fn check_delegation(
+    index: usize,
+    delegation: &Delegation,
+    namespace: &str,
+    parent_fqdn: &FullyQualifiedDomainName,
+    fqdn: &FullyQualifiedDomainName,
+    subject: &Subject,
+) -> DelegationCheck {
+    let covered = delegation.covers_namespace(namespace);
+    let namespace = RuleCheck {
+        rule: if delegation.namespaces.is_empty() {
+            "namespaces (any)".to_string()
+        } else {
+            format!("namespaces [{}]", delegation.namespaces.join(", "))
+        },
+        allowed: covered,
+        reason: if covered {
+            format!("covers {namespace}")
+        } else {
+            format!("does not cover {namespace}")
+        },
+    };
+
+    let rules = match subject {
+        Subject::Record(type_) => delegation
+            .records
+            .iter()
+            .map(|record_delegation| {
+                let pattern = record_delegation.pattern.with_origin(parent_fqdn);
+                let types = if record_delegation.types.is_empty() {
+                    "(any)".to_string()
+                } else {
+                    let types: Vec<_> = record_delegation
+                        .types
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect();
+                    format!("[{}]", types.join(", "))
+                };
+
+                let rule = format!("record pattern {} types {types}", record_delegation.pattern);
+
+                // The verdict is left to the same validation the controllers use,
+                // only the reason is worked out here.
+                let allowed = record_delegation.validate(parent_fqdn, *type_, fqdn);
+                let any_type = RecordDelegation {
+                    pattern: record_delegation.pattern.clone(),
+                    types: Vec::new(),
+                };
+
+                let reason = if allowed {
+                    format!("{pattern} matches {fqdn}")
+                } else if any_type.validate(parent_fqdn, *type_, fqdn) {
+                    format!("type {type_} is not delegated")
+                } else {
+                    format!("{pattern} does not match {fqdn}")
+                };
+
+                RuleCheck {
+                    rule,
+                    allowed,
+                    reason,
+                }
+            })
+            .collect(),
+        Subject::Zone => delegation
+            .zones
+            .iter()
+            .map(|zone_delegation| {
+                let pattern = zone_delegation.with_origin(parent_fqdn);
+                let matches = Delegation {
+                    namespaces: Vec::new(),
+                    zones: vec![zone_delegation.clone()],
+                    records: Vec::new(),
+                }
+                .validate_zone(parent_fqdn, fqdn);
+
+                RuleCheck {
+                    rule: format!("zone pattern {zone_delegation}"),
+                    allowed: matches,
+                    reason: if matches {
+                        format!("{pattern} matches {fqdn}")
+                    } else {
+                        format!("{pattern} does not match {fqdn}")
+                    },
+                }
+            })
+            .collect(),
+    };
+
+    DelegationCheck {
+        index,
+        namespace,
+        rules,
+    }
+}
+
+fn verdict(allowed: bool) -> &'static str {
+    if allowed {
+        "allowed"
+    } else {
+        "denied"
+    }
+}
+
+impl Display for RuleCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.rule,
+            verdict(self.allowed),
+            self.reason
+        )
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind, self.object)?;
+        if let Some(fqdn) = &self.fqdn {
+            write!(f, " ({fqdn})")?;
+        }
+        writeln!(f)?;
+
+        match &self.current_parent {
+            Some(parent) => writeln!(f, "current parent: {}", parent.as_label())?,
+            None => writeln!(f, "current parent: none")?,
+        }
+
+        if let Some(owner) = &self.owner {
+            writeln!(f, "{owner}")?;
+        }
+
+        if self.candidates.is_empty() {
+            writeln!(f, "no candidate parent zones")?;
+        }
+
+        for candidate in &self.candidates {
+            write!(f, "candidate zone {}", candidate.zone)?;
+            match &candidate.fqdn {
+                Some(fqdn) => write!(f, " ({fqdn})")?,
+                None => write!(f, " (no fqdn yet)")?,
+            }
+            match &candidate.role {
+                Role::Referenced => writeln!(f, ", referenced by zoneRef")?,
+                Role::MostImmediate => writeln!(f, ", most immediate parent")?,
+                Role::Shadowed { by } => {
+                    writeln!(f, ", not considered since {by} is more specific")?
+                }
+            }
+
+            if candidate.fqdn.is_some() && candidate.delegations.is_empty() {
+                writeln!(f, "    no delegations")?;
+            }
+
+            for delegation in &candidate.delegations {
+                writeln!(
+                    f,
+                    "    delegation #{}: {}",
+                    delegation.index,
+                    verdict(delegation.allows())
+                )?;
+                writeln!(f, "        {}", delegation.namespace)?;
+
+                if delegation.rules.is_empty() {
+                    writeln!(f, "        no rules for this kind of object")?;
+                }
+                for rule in &delegation.rules {
+                    writeln!(f, "        {rule}")?;
+                }
+            }
+        }
+
+        match &self.resolution {
+            Resolution::Adopted { fqdn, parent } => {
+                writeln!(f, "result: adopted by {} as {fqdn}", parent.as_label())
+            }
+            Resolution::TopLevel { fqdn } => writeln!(f, "result: top level zone {fqdn}"),
+            Resolution::Unadopted { reason, .. } => writeln!(f, "result: not adopted, {reason}"),
+            Resolution::Invalid { reason } => writeln!(f, "result: invalid, {reason}"),
+        }
+    }
+}
+
+/// Fetch the named Record and all Zones from the cluster, and explain its adoption.
+pub async fn record(
+    client: Client,
+    namespace: &str,
+    name: &str,
+) -> Result<Explanation, kube::Error> {
+    let record = Api::<Record>::namespaced(client.clone(), namespace)
+        .get(name)
+        .await?;
+    let zones = Api::<Zone>::all(client)
+        .list(&Default::default())
+        .await?
+        .items;
+
+    Ok(explain_record(&record, &zones))
+}
+
+/// Fetch the named Zone and all other Zones from the cluster, and explain its adoption.
+pub async fn zone(client: Client, namespace: &str, name: &str) -> Result<Explanation, kube::Error> {
+    let zone = Api::<Zone>::namespaced(client.clone(), namespace)
+        .get(name)
+        .await?;
+    let zones = Api::<Zone>::all(client)
+        .list(&Default::default())
+        .await?
+        .items;
+
+    Ok(explain_zone(&zone, &zones))
+}
@@ -1,163 +1,779 @@
-use futures::StreamExt;
-use k8s_openapi::api::networking::v1::Ingress;
-use kubizone_common::{Class, DomainName, Type};
-use std::{net::IpAddr, str::FromStr, sync::Arc, time::Duration};
+use futures::{future, StreamExt};
+use k8s_openapi::{
+    api::networking::v1::{Ingress, IngressClass},
+    serde_json::{json, Map, Value},
+};
+use kubizone_common::{Class, PartiallyQualifiedDomainName, Type};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::IpAddr,
+    ops::Deref,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
 use kube::{
-    api::{ObjectMeta, PatchParams},
-    runtime::{controller::Action, watcher, Controller},
+    api::{DeleteParams, ObjectMeta, Patch},
+    runtime::{
+        controller::{self, Action},
+        reflector::{self, store::WriterDropped, ObjectRef, Store},
+        watcher::{self, watcher},
+        Controller, WatchStreamExt,
+    },
     Api, Client, Resource, ResourceExt,
 };
-use kubizone_crds::v1alpha1::{Record, RecordSpec};
+use kubizone_crds::v1alpha1::{DomainExt as _, Record, RecordSpec, Zone, ZoneRef};
 use tracing::*;
 
+use crate::{
+    backoff::ErrorBackoff,
+    describe,
+    error::{Error, Step},
+    log_dry_run,
+    registry::{find_conflict, Conflicts, Owner, OWNER_ANNOTATION},
+    supervisor::Shutdown,
+    Scope,
+};
+
 #[cfg(feature = "dev")]
 const CONTROLLER_NAME: &str = "dev.kubi.zone/ingress-resolver";
 #[cfg(not(feature = "dev"))]
 const CONTROLLER_NAME: &str = "kubi.zone/ingress-resolver";
 
-pub async fn controller(context: IngressControllerContext) {
-    let ingresses = Api::<Ingress>::all(context.client.clone());
-    let records = Api::<Record>::all(context.client.clone());
-
-    let ingress_controller = Controller::new(ingresses, watcher::Config::default())
-        .owns(records, watcher::Config::default())
-        .shutdown_on_signal()
-        .run(reconcile_ingresses, ingress_error_policy, Arc::new(context))
-        .for_each(|res| async move {
-            match res {
-                Ok((o, _)) => info!("reconciled {}.{}", o.name, o.namespace.unwrap_or_default()),
-                Err(e) => warn!("reconcile failed: {}", e),
+/// Label identifying the Ingress which a Record was generated from.
+#[cfg(feature = "dev")]
+pub const INGRESS_LABEL: &str = "dev.kubi.zone/ingress";
+#[cfg(not(feature = "dev"))]
+pub const INGRESS_LABEL: &str = "kubi.zone/ingress";
+
+/// Label identifying the Ingress host which a Record was generated from.
+#[cfg(feature = "dev")]
+pub const HOST_LABEL: &str = "dev.kubi.zone/ingress-host";
+#[cfg(not(feature = "dev"))]
+pub const HOST_LABEL: &str = "kubi.zone/ingress-host";
+
+/// Label identifying the load balancer address which a Record was generated from.
+#[cfg(feature = "dev")]
+pub const ADDRESS_LABEL: &str = "dev.kubi.zone/ingress-address";
+#[cfg(not(feature = "dev"))]
+pub const ADDRESS_LABEL: &str = "kubi.zone/ingress-address";
+
+/// Annotation on the Ingress reporting, per host, the generated records,
+/// the zones which adopted them, and whether they are currently published.
+#[cfg(feature = "dev")]
+pub const RECORDS_ANNOTATION: &str = "dev.kubi.zone/records";
+#[cfg(not(feature = "dev"))]
+pub const RECORDS_ANNOTATION: &str = "kubi.zone/records";
+
+/// Maximum length of a Kubernetes object name.
+const MAX_NAME_LENGTH: usize = 253;
+
+/// Maximum length of a Kubernetes label value.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Legacy annotation used to specify the class of an Ingress, prior
+/// to the introduction of `.spec.ingressClassName`.
+const INGRESS_CLASS_ANNOTATION: &str = "kubernetes.io/ingress.class";
+
+/// Annotation marking an IngressClass as the cluster default, which
+/// applies to all Ingresses which do not specify a class themselves.
+const DEFAULT_INGRESS_CLASS_ANNOTATION: &str = "ingressclass.kubernetes.io/is-default-class";
+
+pub async fn controller(
+    context: IngressControllerContext,
+    config: controller::Config,
+    shutdown: Shutdown,
+) {
+    let mut ingress_config = watcher::Config::default();
+    if let Some(selector) = context.filter.selector.as_deref() {
+        ingress_config = ingress_config.labels(selector);
+    }
+
+    let client = context.client.clone();
+
+    // All records in scope, used for detecting conflicts with records of other owners.
+    let (records, record_writer) = reflector::store();
+    let (classes, class_writer) = reflector::store();
+    let filters_classes = !context.filter.classes.is_empty();
+
+    let context = Arc::new(Reconciler {
+        context,
+        records,
+        classes,
+    });
+
+    let (store, writer) = reflector::store();
+    let ingress_store = store.clone();
+    let observed = context.clone();
+    let ingresses = context
+        .scope
+        .watch::<Ingress>(client.clone(), ingress_config)
+        .inspect(move |event| {
+            if let Ok(event) = event {
+                observed.backoff.observe(event);
+            }
+        })
+        .reflect(writer)
+        .applied_objects();
+
+    let record_events = context
+        .scope
+        .watch::<Record>(client.clone(), watcher::Config::default())
+        .reflect(record_writer)
+        .touched_objects();
+
+    // Records generated from an ingress are annotated by the zone controller once
+    // adopted and published, which is what the ingress reports on in turn.
+    let mut controller =
+        Controller::for_stream(ingresses, store).watches_stream(record_events, generated_from);
+
+    // IngressClasses are only needed for finding the default class when filtering
+    // by class. Ingresses pick up changes to it once they are requeued. Being
+    // cluster-scoped, they are watched across the cluster regardless of the scope.
+    if filters_classes {
+        let classes = watcher(Api::<IngressClass>::all(client), watcher::Config::default())
+            .reflect(class_writer)
+            .touched_objects();
+
+        controller = controller.watches_stream(classes, |_| None::<ObjectRef<Ingress>>);
+    }
+
+    let controller = controller
+        .with_config(config)
+        .graceful_shutdown_on(shutdown.wait())
+        .run(reconcile_ingresses, ingress_error_policy, context.clone())
+        .for_each(|res| {
+            let context = context.clone();
+            async move {
+                match res {
+                    Ok((o, _)) => {
+                        context.backoff.reset(&o);
+                        info!("reconciled {}.{}", o.name, o.namespace.unwrap_or_default())
+                    }
+                    Err(e) => warn!("reconcile failed: {}", e),
+                }
             }
         });
 
-    ingress_controller.await;
-    warn!("ingress controller exited");
+    tokio::select! {
+        _ = controller => (),
+        _ = sweep_deselected(context.clone(), ingress_store) => (),
+    }
+
+    debug!("ingress controller exited");
 }
 
 pub struct IngressControllerContext {
     pub client: Client,
     pub requeue_time: Duration,
+    pub backoff: ErrorBackoff<Ingress>,
+    pub filter: IngressFilter,
+    pub conflicts: Conflicts,
+    pub scope: Scope,
 }
 
-#[tracing::instrument(name = "ingress", skip_all)]
-async fn reconcile_ingresses(
-    ingress: Arc<Ingress>,
-    ctx: Arc<IngressControllerContext>,
-) -> Result<Action, kube::Error> {
-    let Some(spec) = ingress.spec.as_ref() else {
-        debug!("ingress has no spec (???), requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
-    };
+/// [`IngressControllerContext`] along with the stores maintained by the running controller.
+struct Reconciler {
+    context: IngressControllerContext,
 
-    let Some(rules) = spec.rules.as_ref() else {
-        debug!("ingress contains no rules, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
-    };
+    /// All Records within the scope.
+    records: Store<Record>,
 
-    let Some(status) = ingress.status.as_ref() else {
-        debug!("ingress contains no status, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
-    };
+    /// All IngressClasses, if filtering by class.
+    classes: Store<IngressClass>,
+}
 
-    let Some(lb) = status.load_balancer.as_ref() else {
-        debug!("ingress status contains no loadBalancer segment, requeueing.");
-        return Ok(Action::requeue(ctx.requeue_time));
+impl Reconciler {
+    /// Wait for the stores to be populated, giving up after a few seconds.
+    async fn is_ready(&self) -> bool {
+        let ready = async {
+            self.records.wait_until_ready().await?;
+            if !self.filter.classes.is_empty() {
+                self.classes.wait_until_ready().await?;
+            }
+
+            Ok::<_, WriterDropped>(())
+        };
+
+        matches!(
+            tokio::time::timeout(Duration::from_secs(5), ready).await,
+            Ok(Ok(()))
+        )
+    }
+}
+
+impl Deref for Reconciler {
+    type Target = IngressControllerContext;
+
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+/// Restricts which Ingresses the controller will create Records for.
+#[derive(Debug, Clone, Default)]
+pub struct IngressFilter {
+    /// Ingress class names to create records for. Empty implies *any*.
+    pub classes: Vec<String>,
+
+    /// Namespaces to create records for. Empty implies *any*.
+    pub namespaces: Vec<String>,
+
+    /// Label selector which Ingresses must match, applied to the watcher.
+    pub selector: Option<String>,
+}
+
+impl IngressFilter {
+    /// Check if the given namespace is covered by this filter.
+    pub fn covers_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.is_empty()
+            || self
+                .namespaces
+                .iter()
+                .any(|filtered_namespace| filtered_namespace == namespace)
+    }
+
+    /// Check if the given ingress class is covered by this filter.
+    ///
+    /// Ingresses without a class are only covered if no class filter is set.
+    pub fn covers_class(&self, class: Option<&str>) -> bool {
+        if self.classes.is_empty() {
+            return true;
+        }
+
+        class.is_some_and(|class| {
+            self.classes
+                .iter()
+                .any(|filtered_class| filtered_class == class)
+        })
+    }
+}
+
+/// Determine the class of the given ingress, either through its
+/// `.spec.ingressClassName` or the legacy annotation, falling back
+/// to the cluster's default IngressClass among `classes`, if one exists.
+fn ingress_class(ingress: &Ingress, classes: &Store<IngressClass>) -> Option<String> {
+    if let Some(class) = ingress
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.ingress_class_name.clone())
+        .or_else(|| ingress.annotations().get(INGRESS_CLASS_ANNOTATION).cloned())
+    {
+        return Some(class);
+    }
+
+    classes
+        .state()
+        .into_iter()
+        .find(|class| {
+            class
+                .annotations()
+                .get(DEFAULT_INGRESS_CLASS_ANNOTATION)
+                .is_some_and(|default| default == "true")
+        })
+        .map(|class| class.name_any())
+}
+
+#[tracing::instrument(
+    name = "ingress",
+    skip_all,
+    fields(
+        kind = "Ingress",
+        namespace = ingress.metadata.namespace.as_deref(),
+        object = %ingress.name_any(),
+    )
+)]
+async fn reconcile_ingresses(ingress: Arc<Ingress>, ctx: Arc<Reconciler>) -> Result<Action, Error> {
+    // Conflicts and the default class can only be determined once all records
+    // and IngressClasses have been listed, which takes a moment after startup.
+    if !ctx.is_ready().await {
+        debug!("records or ingress classes have not been listed yet, requeueing.");
+        return Ok(Action::requeue(Duration::from_secs(1)));
+    }
+
+    let owner = Owner::of(ingress.as_ref());
+
+    let records =
+        Api::<Record>::namespaced(ctx.client.clone(), ingress.namespace().as_ref().unwrap());
+
+    let covered = if !ctx
+        .filter
+        .covers_namespace(ingress.namespace().as_deref().unwrap())
+    {
+        debug!(
+            "ingress {} is not in a filtered namespace, ignoring.",
+            ingress.name_any()
+        );
+        false
+    } else if !ctx.filter.classes.is_empty() {
+        let class = ingress_class(&ingress, &ctx.classes);
+
+        let covered = ctx.filter.covers_class(class.as_deref());
+        if !covered {
+            debug!(
+                "ingress {} has class {class:?}, which is not filtered for, ignoring.",
+                ingress.name_any()
+            );
+        }
+        covered
+    } else {
+        true
     };
 
-    let Some(ingresses) = lb.ingress.as_ref() else {
-        debug!("ingress status load balancer contains no ingresses, requeueing.");
+    // All records within the scope, used for detecting whether any other owner has
+    // already claimed one of the names and types we're about to publish, and for
+    // finding the records previously generated from this ingress.
+    let claimed = ctx.records.state();
+
+    // Ingresses which are no longer covered by the filter keep none of their records.
+    if !covered {
+        delete_stale_records(&ingress, &owner, &claimed, &HashSet::new(), &ctx).await?;
         return Ok(Action::requeue(ctx.requeue_time));
-    };
+    }
+
+    // Ingresses without rules or load balancer addresses get no records, but
+    // still have the records previously generated for them deleted below.
+    let rules = ingress
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.rules.as_deref())
+        .unwrap_or_default();
+
+    if rules.is_empty() {
+        debug!("ingress contains no rules.");
+    }
+
+    let ingresses = ingress
+        .status
+        .as_ref()
+        .and_then(|status| status.load_balancer.as_ref())
+        .and_then(|lb| lb.ingress.as_deref())
+        .unwrap_or_default();
+
+    if ingresses.is_empty() {
+        debug!("ingress status contains no load balancer ingresses.");
+    }
 
     let (ipv4_addresses, ipv6_addresses): (Vec<IpAddr>, Vec<IpAddr>) = ingresses
         .iter()
         .filter_map(|ingress| ingress.ip.as_ref())
-        .filter_map(|address| IpAddr::from_str(&address).ok())
+        .filter_map(|address| IpAddr::from_str(address).ok())
         .partition(IpAddr::is_ipv4);
 
+    // Ingress hosts are never fully qualified, but may contain a leading
+    // wildcard segment, in which case a wildcard record is produced.
     let hostnames: Vec<_> = rules
         .iter()
         .filter_map(|rule| rule.host.as_deref())
-        .map(DomainName::try_from)
-        .filter_map(Result::ok)
+        .filter_map(|host| match PartiallyQualifiedDomainName::try_from(host) {
+            Ok(hostname) => Some(hostname),
+            Err(err) => {
+                warn!(
+                    "ingress {} host {host} is not a valid domain name: {err}",
+                    ingress.name_any()
+                );
+                None
+            }
+        })
         .collect();
 
-    let records =
-        Api::<Record>::namespaced(ctx.client.clone(), ingress.namespace().as_ref().unwrap());
+    // Names of all records which should exist for this ingress. Any other records
+    // previously generated from it are stale, and will be deleted.
+    let mut desired = HashSet::new();
+
+    // Generated records and conflicting records for each host, reported
+    // back onto the ingress once all records have been applied.
+    let mut hosts = BTreeMap::<String, HostRecords>::new();
+
     for hostname in hostnames.iter() {
-        let metadata = |addr: &IpAddr| ObjectMeta {
-            name: Some(format!(
-                "{}-{}-{}",
-                ingress.name_any(),
-                hostname.to_string().replace(".", "-"),
-                addr.to_canonical()
-                    .to_string()
-                    .replace(".", "-")
-                    .replace(":", "-")
-            )),
-            owner_references: Some(vec![ingress.owner_ref(&()).unwrap()]),
-            ..Default::default()
-        };
+        let fqdn = hostname.to_fully_qualified();
+        let host = hosts.entry(hostname.to_string()).or_default();
 
-        for ipv4 in ipv4_addresses.iter() {
-            let metadata = metadata(ipv4);
-            info!("creating record {:?}: {hostname} -> {ipv4}", metadata.name);
-            records
-                .patch(
-                    metadata.name.as_deref().unwrap(),
-                    &PatchParams::apply(CONTROLLER_NAME),
-                    &kube::api::Patch::Apply(Record {
-                        metadata: metadata.clone(),
-                        spec: RecordSpec {
-                            domain_name: hostname.to_fully_qualified().into(),
-                            zone_ref: None,
-                            type_: Type::A,
-                            class: Class::IN,
-                            ttl: None,
-                            rdata: ipv4.to_string(),
-                        },
-                        status: None,
-                    }),
-                )
-                .await?;
-        }
+        let addresses = ipv4_addresses
+            .iter()
+            .map(|addr| (addr, Type::A))
+            .chain(ipv6_addresses.iter().map(|addr| (addr, Type::AAAA)));
+
+        for (addr, type_) in addresses {
+            let name = record_name(&ingress.name_any(), &hostname.to_string(), addr);
+
+            if let Some(existing) = find_conflict(&owner, &fqdn, type_, &claimed) {
+                if ctx.scope.dry_run {
+                    info!("dry-run: would report conflict between {owner} and record {existing} for {type_} record {fqdn}");
+                } else {
+                    ctx.conflicts
+                        .report(
+                            ctx.client.clone(),
+                            &owner,
+                            ingress.object_ref(&()),
+                            existing,
+                            &fqdn,
+                            type_,
+                        )
+                        .await
+                        .map_err(|err| Error::api(ingress.as_ref(), Step::ReportConflict, err))?;
+                }
+                host.conflicts.push(existing.to_string());
+                continue;
+            }
+
+            ctx.conflicts.resolve(&owner, &fqdn, type_);
+
+            let metadata = ObjectMeta {
+                name: Some(name.clone()),
+                owner_references: Some(vec![ingress.owner_ref(&()).unwrap()]),
+                annotations: Some(BTreeMap::from([(
+                    OWNER_ANNOTATION.to_string(),
+                    owner.as_annotation(),
+                )])),
+                labels: Some(BTreeMap::from([
+                    (INGRESS_LABEL.to_string(), label_value(&ingress.name_any())),
+                    (HOST_LABEL.to_string(), label_value(&hostname.to_string())),
+                    (
+                        ADDRESS_LABEL.to_string(),
+                        label_value(&addr.to_canonical().to_string()),
+                    ),
+                ])),
+                ..Default::default()
+            };
+
+            info!("creating record {name}: {hostname} -> {addr}");
+            let params = ctx.scope.patch_params(CONTROLLER_NAME);
+            let patch = Patch::Apply(Record {
+                metadata,
+                spec: RecordSpec {
+                    domain_name: fqdn.clone().into(),
+                    zone_ref: None,
+                    type_,
+                    class: Class::IN,
+                    ttl: None,
+                    rdata: addr.to_string(),
+                },
+                status: None,
+            });
 
-        for ipv6 in ipv6_addresses.iter() {
-            let metadata = metadata(ipv6);
-            info!("creating record {:?}: {hostname} -> {ipv6}", metadata.name);
-            records
-                .patch(
-                    metadata.name.as_deref().unwrap(),
-                    &PatchParams::apply(CONTROLLER_NAME),
-                    &kube::api::Patch::Apply(Record {
-                        metadata: metadata.clone(),
-                        spec: RecordSpec {
-                            domain_name: hostname.to_fully_qualified().into(),
-                            zone_ref: None,
-                            type_: Type::AAAA,
-                            class: Class::IN,
-                            ttl: None,
-                            rdata: ipv6.to_string(),
-                        },
-                        status: None,
-                    }),
-                )
-                .await?;
+            log_dry_run(
+                &params,
+                format!("Record {}/{name}", ingress.namespace().unwrap_or_default()),
+                &patch,
+            );
+            let record = records
+                .patch(&name, &params, &patch)
+                .await
+                .map_err(|err| Error::api(ingress.as_ref(), Step::ApplyRecord, err))?;
+
+            host.records.push(record);
+            desired.insert(name);
         }
     }
 
+    // Delete records which were previously generated from this ingress, but
+    // are no longer wanted, either because the hosts or addresses changed,
+    // or because another owner has since claimed them.
+    delete_stale_records(&ingress, &owner, &claimed, &desired, &ctx).await?;
+
+    report_hosts(&ingress, ctx.client.clone(), &ctx.scope, hosts)
+        .await
+        .map_err(|err| Error::api(ingress.as_ref(), Step::UpdateStatus, err))?;
+
     Ok(Action::requeue(ctx.requeue_time))
 }
 
-fn ingress_error_policy(
-    ingress: Arc<Ingress>,
-    error: &kube::Error,
-    _ctx: Arc<IngressControllerContext>,
-) -> Action {
-    error!(
-        "ingress {} reconciliation encountered error: {error}",
+/// Reference to the Ingress which the record was generated from, if any.
+fn generated_from(record: Record) -> Option<ObjectRef<Ingress>> {
+    if !record.labels().contains_key(INGRESS_LABEL) {
+        return None;
+    }
+
+    // The label value is not necessarily the name of the ingress, since long
+    // names are truncated, so the owner reference is used instead.
+    let owner = record.owner_references().iter().find(|owner| {
+        owner.kind == <Ingress as k8s_openapi::Resource>::KIND
+            && owner.api_version == <Ingress as k8s_openapi::Resource>::API_VERSION
+    })?;
+
+    Some(ObjectRef::new(&owner.name).within(record.namespace().as_deref()?))
+}
+
+/// Delete the records among `records` which `owner` previously generated
+/// from the ingress, but which are not among the `desired` record names.
+async fn delete_stale_records(
+    ingress: &Ingress,
+    owner: &Owner,
+    records: &[Arc<Record>],
+    desired: &HashSet<String>,
+    ctx: &Reconciler,
+) -> Result<(), Error> {
+    for stale in records.iter().filter(|record| {
+        owner.owns(record)
+            && record.namespace() == ingress.namespace()
+            && !desired.contains(&record.name_any())
+    }) {
+        info!("deleting stale record {stale}");
+        delete_record(stale, ctx)
+            .await
+            .map_err(|err| Error::api(ingress, Step::DeleteRecord, err))?;
+    }
+
+    Ok(())
+}
+
+/// Delete the records generated from Ingresses which no longer match the
+/// [`IngressFilter::selector`] every `requeue_time`, since such Ingresses drop
+/// out of the watch, and are never reconciled again.
+async fn sweep_deselected(ctx: Arc<Reconciler>, ingresses: Store<Ingress>) {
+    if ctx.filter.selector.is_none() {
+        return future::pending().await;
+    }
+
+    loop {
+        tokio::time::sleep(ctx.requeue_time).await;
+
+        if ingresses.wait_until_ready().await.is_err() || !ctx.is_ready().await {
+            continue;
+        }
+
+        for record in ctx.records.state() {
+            let Some(ingress) = generated_from(record.as_ref().clone()) else {
+                continue;
+            };
+
+            if ingresses.get(&ingress).is_some() {
+                continue;
+            }
+
+            info!(
+                "deleting record {record}, since ingress {ingress} no longer matches the selector"
+            );
+            if let Err(err) = delete_record(&record, &ctx).await {
+                warn!("failed to delete record {record}: {err}");
+            }
+        }
+    }
+}
+
+/// Delete the given generated `record`, unless running in dry-run mode.
+async fn delete_record(record: &Record, ctx: &Reconciler) -> Result<(), kube::Error> {
+    if ctx.scope.dry_run {
+        info!("dry-run: would delete {}", describe(record));
+    }
+
+    Api::<Record>::namespaced(ctx.client.clone(), record.namespace().as_ref().unwrap())
+        .delete(
+            &record.name_any(),
+            &DeleteParams {
+                dry_run: ctx.scope.dry_run,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Records generated for a single ingress host.
+#[derive(Default)]
+struct HostRecords {
+    records: Vec<Record>,
+
+    /// Records of other owners, which prevented records from being generated for the host.
+    conflicts: Vec<String>,
+}
+
+/// Report the records generated for each host onto the ingress' [`RECORDS_ANNOTATION`],
+/// along with the zone which adopted them, and whether they are currently published
+/// in that zone's entries.
+async fn report_hosts(
+    ingress: &Ingress,
+    client: Client,
+    scope: &Scope,
+    hosts: BTreeMap<String, HostRecords>,
+) -> Result<(), kube::Error> {
+    let mut zones = HashMap::<ZoneRef, Option<Zone>>::new();
+    let mut report = Map::new();
+
+    for (host, HostRecords { records, conflicts }) in hosts {
+        let parents: BTreeSet<_> = records.iter().filter_map(Record::parent).collect();
+
+        // Records which have not yet been adopted are not published.
+        let mut published =
+            !records.is_empty() && records.iter().all(|record| record.parent().is_some());
+
+        for parent in &parents {
+            if !zones.contains_key(parent) {
+                let namespace = parent.namespace.as_deref().unwrap_or_default();
+
+                let zone = if scope.covers_namespace(namespace) {
+                    Api::<Zone>::namespaced(client.clone(), namespace)
+                        .get_opt(&parent.name)
+                        .await?
+                } else {
+                    None
+                };
+
+                zones.insert(parent.clone(), zone);
+            }
+
+            published &= records
+                .iter()
+                .filter(|record| record.parent().as_ref() == Some(parent))
+                .all(|record| {
+                    zones[parent].as_ref().is_some_and(|zone| {
+                        zone.status
+                            .iter()
+                            .flat_map(|status| &status.entries)
+                            .any(|entry| {
+                                Some(&entry.fqdn) == record.fqdn()
+                                    && entry.type_ == record.spec.type_
+                                    && entry.rdata == record.spec.rdata
+                            })
+                    })
+                });
+        }
+
+        let mut status = Map::new();
+        status.insert(
+            "records".to_string(),
+            json!(records
+                .iter()
+                .map(ResourceExt::name_any)
+                .collect::<Vec<_>>()),
+        );
+        status.insert(
+            "zones".to_string(),
+            json!(parents.iter().map(ToString::to_string).collect::<Vec<_>>()),
+        );
+        status.insert("published".to_string(), json!(published));
+        if !conflicts.is_empty() {
+            status.insert("conflicts".to_string(), json!(conflicts));
+        }
+
+        report.insert(host, Value::Object(status));
+    }
+
+    let report = Value::Object(report).to_string();
+
+    if ingress.annotations().get(RECORDS_ANNOTATION) == Some(&report) {
+        debug!(
+            "ingress {} records annotation already up to date.",
+            ingress.name_any()
+        );
+        return Ok(());
+    }
+
+    info!(
+        "updating ingress {}'s {RECORDS_ANNOTATION}: {report}",
         ingress.name_any()
     );
-    Action::requeue(Duration::from_secs(60))
+    let params = scope.patch_params(CONTROLLER_NAME);
+    let patch = Patch::Merge(json!({
+        "metadata": {
+            "annotations": {
+                RECORDS_ANNOTATION: report
+            },
+        }
+    }));
+
+    log_dry_run(&params, describe(ingress), &patch);
+    Api::<Ingress>::namespaced(client, ingress.namespace().as_ref().unwrap())
+        .patch_metadata(&ingress.name_any(), &params, &patch)
+        .await?;
+
+    Ok(())
+}
+
+/// Produce the name of the Record generated for the given ingress, host and address.
+///
+/// The name is made up of the human-readable parts, truncated to fit within
+/// the object name length limit, followed by a hash of the untruncated inputs,
+/// making it unlikely for distinct inputs to produce the same name.
+pub fn record_name(ingress: &str, host: &str, addr: &IpAddr) -> String {
+    let addr = addr.to_canonical().to_string();
+
+    with_hash_suffix(
+        &format!(
+            "{}-{}-{}",
+            sanitize(ingress),
+            sanitize(host),
+            sanitize(&addr)
+        ),
+        MAX_NAME_LENGTH,
+        &[ingress, host, &addr],
+    )
+}
+
+/// Produce a valid label value from the given string.
+///
+/// Values which are already valid are used as-is, while any others are
+/// sanitized and truncated, and suffixed with a hash of the original value.
+pub fn label_value(value: &str) -> String {
+    let is_valid = value.len() <= MAX_LABEL_LENGTH
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && value.ends_with(|c: char| c.is_ascii_alphanumeric());
+
+    if is_valid {
+        return value.to_string();
+    }
+
+    with_hash_suffix(&sanitize(value), MAX_LABEL_LENGTH, &[value])
+}
+
+/// Replace all characters which are not lowercase alphanumeric with hyphens.
+///
+/// Wildcards are spelled out, so `*.example.org` and `-.example.org` differ.
+fn sanitize(value: &str) -> String {
+    value
+        .replace('*', "wildcard")
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Truncate `value` such that it fits within `max_length` after appending
+/// a hash of `parts` to it.
+fn with_hash_suffix(value: &str, max_length: usize, parts: &[&str]) -> String {
+    let suffix = format!("{:08x}", stable_hash(parts));
+
+    // Values are sanitized and therefore only contain ASCII,
+    // making it safe to slice at arbitrary positions.
+    let prefix = value[..value.len().min(max_length - suffix.len() - 1)]
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric());
+
+    if prefix.is_empty() {
+        suffix
+    } else {
+        format!("{prefix}-{suffix}")
+    }
+}
+
+/// 32-bit FNV-1a hash of the given parts.
+///
+/// Being only 32 bits wide, distinct parts may produce the same hash, so generated
+/// names only collide if both their truncated prefixes and hashes are equal.
+///
+/// Unlike [`std::collections::hash_map::DefaultHasher`], the output of this hash
+/// is stable across Rust releases, which is required for deterministic names.
+fn stable_hash(parts: &[&str]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+
+    for (i, part) in parts.iter().enumerate() {
+        // Separate parts with a null byte, so ["ab", "c"] and ["a", "bc"] differ.
+        let separator: &[u8] = if i == 0 { &[] } else { &[0] };
+
+        for byte in separator.iter().chain(part.as_bytes()) {
+            hash ^= *byte as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+    }
+
+    hash
+}
+
+fn ingress_error_policy(ingress: Arc<Ingress>, error: &Error, ctx: Arc<Reconciler>) -> Action {
+    ctx.backoff.on_error(ingress.as_ref(), error)
 }
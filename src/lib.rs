@@ -1,26 +1,58 @@
+pub mod backoff;
+pub mod check;
+pub mod config;
+pub mod crds;
+pub mod error;
+pub mod explain;
 pub mod ingress;
 pub mod record;
+pub mod registry;
+pub mod resolution;
+pub mod supervisor;
+pub mod telemetry;
+pub mod tree;
 pub mod zone;
 
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    hash::Hash,
+    sync::Arc,
+};
 
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
 use json_patch::{PatchOperation, RemoveOperation};
 use k8s_openapi::{
     serde::{de::DeserializeOwned, Serialize},
-    serde_json::json,
+    serde_json::{self, json},
     NamespaceResourceScope,
 };
 use kube::{
-    api::{Patch, PatchParams},
+    api::{ListParams, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::{Event, EventType, Recorder, Reporter},
+        reflector::{ObjectRef, Store},
+        watcher::{self, watcher},
+    },
     Api, Client, Resource, ResourceExt,
 };
 use kubizone_common::FullyQualifiedDomainName;
 use kubizone_crds::{
-    v1alpha1::{DomainExt, ZoneRef},
+    v1alpha1::{DomainExt, Record, Zone, ZoneRef},
     PARENT_ZONE_LABEL,
 };
 use tracing::{debug, info};
 
+use crate::{
+    backoff::{jitter, ErrorBackoff},
+    error::{Error, Step},
+    resolution::{Publication, NORMALIZED_TTL_ANNOTATION, PUBLISHED_ANNOTATION},
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Effect {
     None,
@@ -33,19 +65,456 @@ impl Effect {
     }
 }
 
+/// Set of namespaces and objects which the controllers watch and operate on.
+///
+/// An empty scope covers the entire cluster, requiring cluster-wide
+/// list and watch permissions.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub namespaces: Vec<String>,
+
+    /// Label selector restricting which Zones are reconciled and
+    /// considered as parents, for sharding zones across instances.
+    ///
+    /// Zones and Records are only ever resolved against parent zones within
+    /// the same shard, so each zone must share its shard with its parent,
+    /// and with all records and zones which it is meant to adopt.
+    pub zone_selector: Option<String>,
+
+    /// Label selector restricting which Records are reconciled and
+    /// included in zone entries, for sharding records across instances.
+    /// See [`Scope::zone_selector`] for how records are resolved.
+    pub record_selector: Option<String>,
+
+    /// Name of the shard, used to distinguish the field managers
+    /// of instances operating on different shards.
+    pub shard: Option<String>,
+
+    /// Send all patches with server-side dry-run, logging
+    /// them instead of persisting any changes.
+    pub dry_run: bool,
+}
+
+impl Scope {
+    /// Check if the given namespace is covered by this scope.
+    pub fn covers_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.is_empty()
+            || self
+                .namespaces
+                .iter()
+                .any(|scoped_namespace| scoped_namespace == namespace)
+    }
+
+    /// Namespaces to run individual watchers for, or a single `None`
+    /// if the scope covers the entire cluster.
+    pub fn watched(&self) -> Vec<Option<String>> {
+        if self.namespaces.is_empty() {
+            vec![None]
+        } else {
+            self.namespaces.iter().cloned().map(Some).collect()
+        }
+    }
+
+    /// Field manager name to use for the given controller within this shard.
+    pub fn field_manager(&self, controller_name: &str) -> String {
+        match &self.shard {
+            Some(shard) => format!("{controller_name}-{shard}"),
+            None => controller_name.to_string(),
+        }
+    }
+
+    /// Server-side apply parameters for the given controller within this
+    /// shard, which only validate the patch when running in dry-run mode.
+    pub fn patch_params(&self, controller_name: &str) -> PatchParams {
+        let params = PatchParams::apply(&self.field_manager(controller_name));
+
+        if self.dry_run {
+            params.dry_run()
+        } else {
+            params
+        }
+    }
+
+    /// Watcher configuration for Zones within this scope.
+    pub fn zone_config(&self) -> watcher::Config {
+        match &self.zone_selector {
+            Some(selector) => watcher::Config::default().labels(selector),
+            None => watcher::Config::default(),
+        }
+    }
+
+    /// Watcher configuration for Records within this scope.
+    pub fn record_config(&self) -> watcher::Config {
+        match &self.record_selector {
+            Some(selector) => watcher::Config::default().labels(selector),
+            None => watcher::Config::default(),
+        }
+    }
+
+    /// List parameters for Zones within this scope.
+    pub fn zone_params(&self) -> ListParams {
+        match &self.zone_selector {
+            Some(selector) => ListParams::default().labels(selector),
+            None => ListParams::default(),
+        }
+    }
+
+    /// List parameters for Records within this scope, further
+    /// restricted by the `selector`, if any.
+    pub fn record_params(&self, selector: Option<&str>) -> ListParams {
+        let selector = [self.record_selector.as_deref(), selector]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(",");
+
+        if selector.is_empty() {
+            ListParams::default()
+        } else {
+            ListParams::default().labels(&selector)
+        }
+    }
+
+    /// Fetch the named Zone, if it exists and is within this scope.
+    pub async fn get_zone(
+        &self,
+        client: Client,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<Zone>, kube::Error> {
+        if !self.covers_namespace(namespace) {
+            return Ok(None);
+        }
+
+        // Label selectors can only be applied when listing, so fetch
+        // the zone by listing with a field selector on its name.
+        Ok(Api::<Zone>::namespaced(client, namespace)
+            .list(&self.zone_params().fields(&format!("metadata.name={name}")))
+            .await?
+            .items
+            .into_iter()
+            .next())
+    }
+
+    /// Produce an Api for the given watched namespace, as returned by [`Scope::watched`].
+    pub fn api<K>(client: Client, namespace: Option<&str>) -> Api<K>
+    where
+        K: Resource<Scope = NamespaceResourceScope>,
+        <K as Resource>::DynamicType: Default,
+    {
+        match namespace {
+            Some(namespace) => Api::namespaced(client, namespace),
+            None => Api::all(client),
+        }
+    }
+
+    /// Watch resources across all namespaces covered by this scope as a single
+    /// stream, which can be shared by one reflector. See [`merge_watches`].
+    pub fn watch<K>(
+        &self,
+        client: Client,
+        config: watcher::Config,
+    ) -> BoxStream<'static, Result<watcher::Event<K>, watcher::Error>>
+    where
+        K: Resource<Scope = NamespaceResourceScope> + Clone + Debug + DeserializeOwned,
+        K: Send + Sync + 'static,
+        K::DynamicType: Default + Eq + Hash + Clone,
+    {
+        let watched = self.watched();
+        if watched.len() == 1 {
+            return watcher(Scope::api::<K>(client, watched[0].as_deref()), config).boxed();
+        }
+
+        merge_watches(
+            watched
+                .into_iter()
+                .map(|namespace| {
+                    watcher(
+                        Scope::api::<K>(client.clone(), namespace.as_deref()),
+                        config.clone(),
+                    )
+                    .boxed()
+                })
+                .collect(),
+        )
+        .boxed()
+    }
+
+    /// List resources across all namespaces covered by this scope.
+    pub async fn list<K>(&self, client: Client, params: &ListParams) -> Result<Vec<K>, kube::Error>
+    where
+        K: Resource<Scope = NamespaceResourceScope> + Clone + Debug + DeserializeOwned,
+        <K as Resource>::DynamicType: Default,
+    {
+        let mut resources = Vec::new();
+        for namespace in self.watched() {
+            resources.extend(
+                Scope::api::<K>(client.clone(), namespace.as_deref())
+                    .list(params)
+                    .await?
+                    .items,
+            );
+        }
+
+        Ok(resources)
+    }
+}
+
+/// Translate the initial listings of a single namespace's watch into individual
+/// applies, followed by deletes for the objects which disappeared in the meantime.
+///
+/// A reflector replaces its entire store once a listing completes, so watches of
+/// several namespaces could otherwise not share a single store, since the listing
+/// of one namespace would drop the objects of all others.
+pub fn without_relists<K>(
+    events: impl Stream<Item = Result<watcher::Event<K>, watcher::Error>>,
+) -> impl Stream<Item = Result<watcher::Event<K>, watcher::Error>>
+where
+    K: Resource + Clone,
+    K::DynamicType: Default + Eq + Hash + Clone,
+{
+    let mut relists = Relists::default();
+    events.flat_map(move |event| stream::iter(relists.translate(event)))
+}
+
+/// Merge the watches of several namespaces into a single stream, translating
+/// their listings as [`without_relists`] does.
+///
+/// Once every namespace has been listed, the objects of all of them are emitted
+/// as a single listing, so that a reflector's store is only marked as ready once
+/// it holds the objects of every namespace.
+pub fn merge_watches<K, S>(
+    watches: Vec<S>,
+) -> impl Stream<Item = Result<watcher::Event<K>, watcher::Error>>
+where
+    K: Resource + Clone,
+    K::DynamicType: Default + Eq + Hash + Clone,
+    S: Stream<Item = Result<watcher::Event<K>, watcher::Error>> + Unpin,
+{
+    let mut relists: Vec<Relists<K>> = watches.iter().map(|_| Relists::default()).collect();
+    let mut listed = vec![false; watches.len()];
+    let mut ready = false;
+
+    let watches = watches
+        .into_iter()
+        .enumerate()
+        .map(|(index, watch)| watch.map(move |event| (index, event)));
+
+    stream::select_all(watches).flat_map(move |(index, event)| {
+        let done = matches!(event, Ok(watcher::Event::InitDone));
+        let mut events = relists[index].translate(event);
+
+        if done && !ready {
+            listed[index] = true;
+
+            if listed.iter().all(|listed| *listed) {
+                ready = true;
+                events.push(Ok(watcher::Event::Init));
+                events.extend(
+                    relists
+                        .iter()
+                        .flat_map(|relists| relists.known.values().cloned())
+                        .map(|object| Ok(watcher::Event::InitApply(object))),
+                );
+                events.push(Ok(watcher::Event::InitDone));
+            }
+        }
+
+        stream::iter(events)
+    })
+}
+
+/// Objects known from a single watch, and those seen during its current listing, if any.
+struct Relists<K: Resource> {
+    known: HashMap<ObjectRef<K>, K>,
+    relisted: Option<HashSet<ObjectRef<K>>>,
+}
+
+impl<K: Resource> Default for Relists<K> {
+    fn default() -> Self {
+        Relists {
+            known: HashMap::new(),
+            relisted: None,
+        }
+    }
+}
+
+impl<K> Relists<K>
+where
+    K: Resource + Clone,
+    K::DynamicType: Default + Eq + Hash + Clone,
+{
+    fn translate(
+        &mut self,
+        event: Result<watcher::Event<K>, watcher::Error>,
+    ) -> Vec<Result<watcher::Event<K>, watcher::Error>> {
+        match event {
+            Ok(watcher::Event::Init) => {
+                self.relisted = Some(HashSet::new());
+                vec![]
+            }
+            Ok(watcher::Event::InitApply(object) | watcher::Event::Apply(object)) => {
+                let object_ref = ObjectRef::from_obj(&object);
+                if let Some(relisted) = self.relisted.as_mut() {
+                    relisted.insert(object_ref.clone());
+                }
+
+                self.known.insert(object_ref, object.clone());
+                vec![Ok(watcher::Event::Apply(object))]
+            }
+            Ok(watcher::Event::Delete(object)) => {
+                self.known.remove(&ObjectRef::from_obj(&object));
+                vec![Ok(watcher::Event::Delete(object))]
+            }
+            Ok(watcher::Event::InitDone) => {
+                let relisted = self.relisted.take().unwrap_or_default();
+                let gone: Vec<_> = self
+                    .known
+                    .keys()
+                    .filter(|object_ref| !relisted.contains(*object_ref))
+                    .cloned()
+                    .collect();
+
+                gone.into_iter()
+                    .filter_map(|object_ref| self.known.remove(&object_ref))
+                    .map(|object| Ok(watcher::Event::Delete(object)))
+                    .collect()
+            }
+            Err(err) => vec![Err(err)],
+        }
+    }
+}
+
+/// References to the objects in `store` which are children of `zone`, either by
+/// label or by referring to it through the zoneRef returned by `zone_ref`.
+///
+/// Used for reconciling the children of a zone as soon as it changes, instead
+/// of waiting for them to be requeued.
+pub fn children_in<K>(
+    store: &Store<K>,
+    zone: &Zone,
+    zone_ref: impl Fn(&K) -> Option<&ZoneRef>,
+) -> Vec<ObjectRef<K>>
+where
+    K: Resource + DomainExt + Clone + 'static,
+    K::DynamicType: Default + Eq + Hash + Clone,
+{
+    store
+        .state()
+        .iter()
+        .filter(|child| resolution::is_child(child.as_ref(), zone_ref(child), zone))
+        .map(|child| ObjectRef::from_obj(child.as_ref()))
+        .collect()
+}
+
+/// Report the rejection of `resource` as either invalid or unadopted, which is
+/// not a failure of the reconcile itself, and retry it after the rejected delay
+/// of the `backoff` policy.
+///
+/// Zone and Record status is defined by the upstream CRDs and has no conditions,
+/// so the rejection is published as an Event on the resource instead, once for
+/// every distinct reason. All other errors are passed on as they are.
+pub async fn report_rejections<K>(
+    result: Result<Action, Error>,
+    client: Client,
+    reporter: &str,
+    backoff: &ErrorBackoff<K>,
+    resource: &K,
+    dry_run: bool,
+) -> Result<Action, Error>
+where
+    K: Resource<DynamicType = ()> + Display,
+{
+    let object = ObjectRef::from_obj(resource);
+
+    let error = match result {
+        Ok(action) => {
+            backoff.accepted(&object);
+            return Ok(action);
+        }
+        Err(error) => error,
+    };
+
+    let Some(reason) = error.rejection().map(str::to_string) else {
+        return Err(error);
+    };
+
+    let delay = jitter(backoff.policy.rejected_delay);
+    info!(reason = error.reason(), "{error}, retrying in {delay:.1?}");
+
+    if !backoff.is_reported(&object, &reason) {
+        if dry_run {
+            info!(
+                "dry-run: would publish {} event on {resource}: {reason}",
+                error.reason()
+            );
+        } else {
+            Recorder::new(client, Reporter::from(reporter), resource.object_ref(&()))
+                .publish(Event {
+                    type_: EventType::Warning,
+                    reason: error.reason().to_string(),
+                    note: Some(reason.clone()),
+                    action: "Adopt".to_string(),
+                    secondary: None,
+                })
+                .await
+                .map_err(|err| Error::api(resource, Step::ReportRejection, err))?;
+        }
+
+        backoff.mark_reported(object, reason);
+    }
+
+    Ok(Action::requeue(delay))
+}
+
+/// Log the exact patch about to be sent to `target`, if `params` are for a dry-run.
+pub fn log_dry_run<P: Serialize + Debug>(
+    params: &PatchParams,
+    target: impl Display,
+    patch: &Patch<P>,
+) {
+    if !params.dry_run {
+        return;
+    }
+
+    let body = match patch {
+        Patch::Apply(patch) | Patch::Merge(patch) | Patch::Strategic(patch) => {
+            serde_json::to_string(patch)
+        }
+        Patch::Json(patch) => serde_json::to_string(patch),
+        patch => Ok(format!("{patch:?}")),
+    }
+    .unwrap_or_else(|err| format!("<unserializable patch: {err}>"));
+
+    info!("dry-run: would patch {target} with {body}");
+}
+
+/// Kind, namespace and name of the resource, for use in log messages.
+pub fn describe<R>(resource: &R) -> String
+where
+    R: Resource,
+    <R as Resource>::DynamicType: Default,
+{
+    format!(
+        "{} {}/{}",
+        R::kind(&R::DynamicType::default()),
+        resource.namespace().unwrap_or_default(),
+        resource.name_any()
+    )
+}
+
 /// Configure the kubi.zone/parent-zone label for the resource.
 ///
 /// If `parent` is none, the label will be deleted instead.
 pub async fn set_parent<R>(
-    controller_name: &'static str,
+    params: &PatchParams,
     client: Client,
     resource: &Arc<R>,
     parent: Option<ZoneRef>,
-) -> Result<Effect, kube::Error>
+) -> Result<Effect, Error>
 where
     R: Resource + ResourceExt + Clone + Debug + DeserializeOwned + Serialize,
-    R: Resource<Scope = NamespaceResourceScope>,
-    <R as Resource>::DynamicType: Default,
+    R: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
 {
     match (resource.labels().get(PARENT_ZONE_LABEL), parent) {
         (None, None) => {
@@ -62,17 +531,17 @@ where
                 R::kind(&R::DynamicType::default()),
                 resource.name_any()
             );
+            let patch = Patch::<R>::Json(json_patch::Patch(vec![PatchOperation::Remove(
+                RemoveOperation {
+                    path: jsonptr::Pointer::new(["metadata", "labels", PARENT_ZONE_LABEL]),
+                },
+            )]));
+
+            log_dry_run(params, describe(resource.as_ref()), &patch);
             Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
-                .patch_metadata(
-                    &resource.name_any(),
-                    &PatchParams::apply(controller_name),
-                    &Patch::<R>::Json(json_patch::Patch(vec![PatchOperation::Remove(
-                        RemoveOperation {
-                            path: jsonptr::Pointer::new(&["metadata", "labels", PARENT_ZONE_LABEL]),
-                        },
-                    )])),
-                )
-                .await?;
+                .patch_metadata(&resource.name_any(), params, &patch)
+                .await
+                .map_err(|err| Error::api(resource.as_ref(), Step::SetParent, err))?;
 
             Ok(Effect::Changed)
         }
@@ -82,19 +551,19 @@ where
                 R::kind(&R::DynamicType::default()),
                 resource.name_any()
             );
+            let patch = Patch::Merge(json!({
+                "metadata": {
+                    "labels": {
+                        PARENT_ZONE_LABEL: desired.as_label()
+                    },
+                }
+            }));
+
+            log_dry_run(params, describe(resource.as_ref()), &patch);
             Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
-                .patch_metadata(
-                    &resource.name_any(),
-                    &PatchParams::apply(controller_name),
-                    &Patch::Merge(json!({
-                        "metadata": {
-                            "labels": {
-                                PARENT_ZONE_LABEL: desired.as_label()
-                            },
-                        }
-                    })),
-                )
-                .await?;
+                .patch_metadata(&resource.name_any(), params, &patch)
+                .await
+                .map_err(|err| Error::api(resource.as_ref(), Step::SetParent, err))?;
 
             Ok(Effect::Changed)
         }
@@ -102,15 +571,14 @@ where
 }
 
 async fn set_fqdn<R>(
-    controller_name: &'static str,
+    params: &PatchParams,
     client: Client,
     resource: &Arc<R>,
     fqdn: &FullyQualifiedDomainName,
-) -> Result<Effect, kube::Error>
+) -> Result<Effect, Error>
 where
     R: Resource + DomainExt + DeserializeOwned,
-    R: Resource<Scope = NamespaceResourceScope>,
-    <R as Resource>::DynamicType: Default,
+    R: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
 {
     if resource.fqdn() == Some(fqdn) {
         debug!(
@@ -128,17 +596,112 @@ where
         resource.name_any(),
         fqdn
     );
+    let patch = Patch::Merge(json!({
+        "status": {
+            "fqdn": fqdn,
+        }
+    }));
+
+    log_dry_run(params, describe(resource.as_ref()), &patch);
     Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
-        .patch_status(
-            &resource.name_any(),
-            &PatchParams::apply(controller_name),
-            &Patch::Merge(json!({
-                "status": {
-                    "fqdn": fqdn,
-                }
-            })),
-        )
-        .await?;
+        .patch_status(&resource.name_any(), params, &patch)
+        .await
+        .map_err(|err| Error::api(resource.as_ref(), Step::SetFqdn, err))?;
+
+    Ok(Effect::Changed)
+}
+
+/// Remove the fqdn from the resource's status, if it has one.
+async fn clear_fqdn<R>(
+    params: &PatchParams,
+    client: Client,
+    resource: &Arc<R>,
+) -> Result<Effect, Error>
+where
+    R: Resource + DomainExt + DeserializeOwned,
+    R: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
+{
+    if resource.fqdn().is_none() {
+        return Ok(Effect::None);
+    }
+
+    info!("clearing fqdn for {} {}", R::kind(&()), resource.name_any());
+    let patch = Patch::Merge(json!({
+        "status": {
+            "fqdn": null,
+        }
+    }));
+
+    log_dry_run(params, describe(resource.as_ref()), &patch);
+    Api::<R>::namespaced(client, resource.namespace().as_ref().unwrap())
+        .patch_status(&resource.name_any(), params, &patch)
+        .await
+        .map_err(|err| Error::api(resource.as_ref(), Step::SetFqdn, err))?;
+
+    Ok(Effect::Changed)
+}
+
+/// Remove the [`PUBLISHED_ANNOTATION`] from the record, unless it reports the
+/// record as published by its current `parent`, since no other zone publishes it.
+async fn withdraw_publication(
+    params: &PatchParams,
+    client: Client,
+    record: &Record,
+    parent: Option<&ZoneRef>,
+) -> Result<Effect, Error> {
+    if !record.annotations().contains_key(PUBLISHED_ANNOTATION) {
+        return Ok(Effect::None);
+    }
+
+    if let (Some(publication), Some(parent)) = (Publication::of(record), parent) {
+        if publication.zone == parent.as_label() {
+            return Ok(Effect::None);
+        }
+    }
+
+    info!("withdrawing publication of record {record}");
+    let patch = Patch::Merge(json!({
+        "metadata": {
+            "annotations": {
+                PUBLISHED_ANNOTATION: null
+            },
+        }
+    }));
+
+    log_dry_run(params, describe(record), &patch);
+    Api::<Record>::namespaced(client, record.namespace().as_ref().unwrap())
+        .patch_metadata(&record.name_any(), params, &patch)
+        .await
+        .map_err(|err| Error::api(record, Step::WithdrawPublication, err))?;
+
+    Ok(Effect::Changed)
+}
+
+/// Remove the [`NORMALIZED_TTL_ANNOTATION`] from a record which is no longer
+/// adopted, since no zone publishes it with the normalized TTL anymore.
+async fn clear_normalized_ttl(
+    params: &PatchParams,
+    client: Client,
+    record: &Record,
+) -> Result<Effect, Error> {
+    if !record.annotations().contains_key(NORMALIZED_TTL_ANNOTATION) {
+        return Ok(Effect::None);
+    }
+
+    info!("clearing normalized ttl of record {record}");
+    let patch = Patch::Merge(json!({
+        "metadata": {
+            "annotations": {
+                NORMALIZED_TTL_ANNOTATION: null
+            },
+        }
+    }));
+
+    log_dry_run(params, describe(record), &patch);
+    Api::<Record>::namespaced(client, record.namespace().as_ref().unwrap())
+        .patch_metadata(&record.name_any(), params, &patch)
+        .await
+        .map_err(|err| Error::api(record, Step::FlagTtl, err))?;
 
     Ok(Effect::Changed)
 }
@@ -1,11 +1,20 @@
+use std::num::{NonZeroU16, NonZeroU64, NonZeroUsize};
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::time::Duration;
 
-use clap::{command, Parser, Subcommand};
-use futures::{stream::FuturesUnordered, Future};
-use ingress::IngressControllerContext;
-use kube::Client;
+use backoff::{BackoffPolicy, ErrorBackoff};
+use clap::{Parser, Subcommand};
+use config::{Config, ConfigError};
+use futures::{stream::FuturesUnordered, Future, StreamExt};
+use ingress::{IngressControllerContext, IngressFilter};
+use kube::{runtime::controller, Client};
+use kubizone_common::FullyQualifiedDomainName;
 use record::RecordControllerContext;
+use resolution::{SoaDefaults, TtlPolicy};
+use supervisor::{supervise, RestartPolicy, Shutdown, Unrecoverable};
+use telemetry::LogFormat;
+use tracing::{error, warn};
 use zone::ZoneControllerContext;
 
 pub use kubizone::*;
@@ -15,64 +24,565 @@ pub use kubizone::*;
 struct Args {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Format of log output. Log levels are controlled through `RUST_LOG`.
+    #[arg(global = true, env, long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Export traces to the OpenTelemetry collector listening for
+    /// OTLP/HTTP at this address, such as `http://localhost:4318`.
+    #[arg(global = true, env, long)]
+    otlp_endpoint: Option<String>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
-    Reconcile {
-        /// Default time to wait between requeuing resources.
-        #[arg(env, long, default_value_t = 30)]
-        requeue_time_secs: u64,
-
-        /// If enabled, controller will create Records for all
-        /// ingresses based on its hosts and loadBalancer settings.
-        #[arg(env, long, default_value_t = false)]
-        ingress_record_creation: bool,
+    Reconcile(Box<ReconcileArgs>),
+    /// Work with configuration files.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Resolve Zone and Record manifests without a cluster, printing the
+    /// resulting fqdn, parent and entries of every zone, along with every
+    /// rejected object. Exits with a non-zero status if any Record is rejected.
+    Check {
+        /// YAML file, or directory to search for YAML files.
+        path: PathBuf,
+
+        /// TTL to publish all records of an RRset with, if they differ.
+        #[arg(long, value_enum, default_value_t = TtlPolicy::Minimum)]
+        rrset_ttl: TtlPolicy,
+
+        /// Primary nameserver published in the SOA record of every zone.
+        /// Defaults to `ns.` followed by the zone's fqdn.
+        #[arg(long, value_parser = fqdn)]
+        soa_nameserver: Option<FullyQualifiedDomainName>,
+
+        /// Mailbox of the person responsible for every zone, published in
+        /// its SOA record. Defaults to `noc.` followed by the zone's fqdn.
+        #[arg(long, value_parser = fqdn)]
+        soa_mailbox: Option<FullyQualifiedDomainName>,
+    },
+    /// Manage the Zone and Record CustomResourceDefinitions
+    /// for the API version this binary was built for.
+    Crds {
+        #[command(subcommand)]
+        command: CrdsCommand,
     },
+    /// Explain why a Record or Zone is, or is not, adopted by listing every
+    /// candidate parent zone and evaluating each of their delegations.
+    Explain {
+        #[command(subcommand)]
+        command: ExplainCommand,
+    },
+    /// Print the hierarchy of Zones and their adopted child Zones and
+    /// Records across the cluster, along with unadopted and orphaned objects.
+    Tree {
+        /// Output format.
+        #[arg(short, long, value_enum, default_value_t = Output::Text)]
+        output: Output,
+    },
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Output {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum ExplainCommand {
+    /// Explain the adoption of a Record.
+    Record {
+        /// Record to explain, as `<namespace>/<name>` or just `<name>`
+        /// for records in the client's default namespace.
+        object: String,
+    },
+    /// Explain the adoption of a Zone.
+    Zone {
+        /// Zone to explain, as `<namespace>/<name>` or just `<name>`
+        /// for zones in the client's default namespace.
+        object: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CrdsCommand {
+    /// Print the CustomResourceDefinitions as YAML.
+    Print,
+    /// Install or update the CustomResourceDefinitions in the cluster
+    /// using server-side apply. Existing resources are left untouched.
+    Install,
+    /// Verify that the CustomResourceDefinitions installed in the
+    /// cluster match the ones this binary expects.
+    Check,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Check that a configuration file can be parsed, reporting
+    /// the line and column of any errors.
+    Validate {
+        /// Path to the YAML or TOML configuration file.
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct ReconcileArgs {
+    /// Path to a YAML or TOML configuration file. Flags and
+    /// environment variables override values from the file.
+    #[arg(env = "KUBIZONE_CONFIG", long)]
+    config: Option<PathBuf>,
+
+    /// Run the zone controller. Enabled by default.
+    #[arg(env, long, num_args = 0..=1, default_missing_value = "true")]
+    zone_controller: Option<bool>,
+
+    /// Run the record controller. Enabled by default.
+    #[arg(env, long, num_args = 0..=1, default_missing_value = "true")]
+    record_controller: Option<bool>,
+
+    /// Default time to wait between requeuing resources. Defaults to 30.
+    #[arg(env, long)]
+    requeue_time_secs: Option<NonZeroU64>,
+
+    /// If enabled, controller will create Records for all
+    /// ingresses based on its hosts and loadBalancer settings.
+    #[arg(env, long, num_args = 0..=1, default_missing_value = "true")]
+    ingress_record_creation: Option<bool>,
+
+    /// Only watch and operate within this namespace. Can be specified
+    /// multiple times. Defaults to the entire cluster.
+    ///
+    /// Zones referenced through zoneRefs outside of the watched
+    /// namespaces are treated as unreachable.
+    ///
+    /// IngressClasses are cluster-scoped, so `--ingress-class` still
+    /// requires a ClusterRole allowing `list` and `watch` on them.
+    #[arg(env, long, value_delimiter = ',')]
+    watch_namespace: Vec<String>,
+
+    /// Only consider Zones matching this label selector. Zones outside
+    /// of the selector are neither reconciled nor used as parents, so
+    /// zones must be sharded along with the records they adopt.
+    #[arg(env, long)]
+    zone_selector: Option<String>,
+
+    /// Only consider Records matching this label selector. Records outside
+    /// of the selector are neither reconciled nor included in zones.
+    #[arg(env, long)]
+    record_selector: Option<String>,
+
+    /// Name of the shard operated by this instance. Used to keep the
+    /// field managers of instances operating on different shards apart.
+    #[arg(env, long)]
+    shard: Option<String>,
+
+    /// Only create Records for ingresses of this class. Can be
+    /// specified multiple times. Defaults to all classes.
+    ///
+    /// Requires `list` and `watch` on IngressClasses across the cluster,
+    /// for finding the default class, even with `--watch-namespace`.
+    #[arg(env, long, value_delimiter = ',')]
+    ingress_class: Vec<String>,
+
+    /// Only create Records for ingresses in this namespace. Can be
+    /// specified multiple times. Defaults to all namespaces.
+    #[arg(env, long, value_delimiter = ',')]
+    ingress_namespace: Vec<String>,
+
+    /// Only create Records for ingresses matching this label selector.
+    #[arg(env, long)]
+    ingress_selector: Option<String>,
+
+    /// Run the controllers on this many threads. Defaults to
+    /// running everything on a single thread.
+    #[arg(env, long)]
+    worker_threads: Option<NonZeroUsize>,
+
+    /// Maximum number of concurrent zone reconciles. Defaults to unlimited.
+    #[arg(env, long)]
+    zone_concurrency: Option<NonZeroU16>,
+
+    /// Maximum number of concurrent record reconciles. Defaults to unlimited.
+    #[arg(env, long)]
+    record_concurrency: Option<NonZeroU16>,
+
+    /// Maximum number of concurrent ingress reconciles. Defaults to unlimited.
+    #[arg(env, long)]
+    ingress_concurrency: Option<NonZeroU16>,
+
+    /// Number of consecutive restarts after which a failing controller
+    /// is considered unrecoverable, and the process exits. Defaults to 5.
+    #[arg(env, long)]
+    max_restarts: Option<u32>,
+
+    /// Log the patches the controllers would send and validate them using
+    /// server-side dry-run, without persisting any changes.
+    #[arg(env, long, num_args = 0..=1, default_missing_value = "true")]
+    dry_run: Option<bool>,
+
+    /// TTL to publish all records of an RRset with, if they differ.
+    /// Records whose TTL was overridden are annotated with
+    /// `kubi.zone/normalized-ttl`. Defaults to minimum.
+    #[arg(env, long, value_enum)]
+    rrset_ttl: Option<TtlPolicy>,
+
+    /// Primary nameserver published in the SOA record of every zone.
+    /// Defaults to `ns.` followed by the zone's fqdn.
+    #[arg(env, long, value_parser = fqdn)]
+    soa_nameserver: Option<FullyQualifiedDomainName>,
+
+    /// Mailbox of the person responsible for every zone, published in
+    /// its SOA record. Defaults to `noc.` followed by the zone's fqdn.
+    #[arg(env, long, value_parser = fqdn)]
+    soa_mailbox: Option<FullyQualifiedDomainName>,
+}
+
+impl ReconcileArgs {
+    /// Read the configuration file, if any, and override
+    /// its values with the flags which were specified.
+    fn into_config(self) -> Result<Config, ConfigError> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        config.requeue_time_secs = self.requeue_time_secs.or(config.requeue_time_secs);
+        config.worker_threads = self.worker_threads.or(config.worker_threads);
+        config.max_restarts = self.max_restarts.or(config.max_restarts);
+        config.dry_run = self.dry_run.unwrap_or(config.dry_run);
+        config.rrset_ttl = self.rrset_ttl.or(config.rrset_ttl);
+        config.soa.nameserver = self.soa_nameserver.or(config.soa.nameserver);
+        config.soa.mailbox = self.soa_mailbox.or(config.soa.mailbox);
+
+        config.controllers.zone = self.zone_controller.unwrap_or(config.controllers.zone);
+        config.controllers.record = self.record_controller.unwrap_or(config.controllers.record);
+        config.controllers.ingress = self.ingress_record_creation.or(config.controllers.ingress);
+        config.concurrency.zone = self.zone_concurrency.or(config.concurrency.zone);
+        config.concurrency.record = self.record_concurrency.or(config.concurrency.record);
+        config.concurrency.ingress = self.ingress_concurrency.or(config.concurrency.ingress);
+
+        config.scope.namespaces = or_config(self.watch_namespace, config.scope.namespaces);
+        config.scope.zone_selector = self.zone_selector.or(config.scope.zone_selector);
+        config.scope.record_selector = self.record_selector.or(config.scope.record_selector);
+        config.scope.shard = self.shard.or(config.scope.shard);
+
+        config.ingress.classes = or_config(self.ingress_class, config.ingress.classes);
+        config.ingress.namespaces = or_config(self.ingress_namespace, config.ingress.namespaces);
+        config.ingress.selector = self.ingress_selector.or(config.ingress.selector);
+
+        Ok(config)
+    }
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    tracing_subscriber::fmt::init();
+/// Parse a fully qualified domain name given as a flag.
+fn fqdn(value: &str) -> Result<FullyQualifiedDomainName, String> {
+    FullyQualifiedDomainName::try_from(value).map_err(|err| err.to_string())
+}
+
+/// Pick the flag value if specified, otherwise fall back to the configuration file.
+fn or_config<T>(flag: Vec<T>, config: Vec<T>) -> Vec<T> {
+    if flag.is_empty() {
+        config
+    } else {
+        flag
+    }
+}
+
+fn main() {
     let args = Args::parse();
+    let telemetry =
+        telemetry::init(args.log_format, args.otlp_endpoint.as_deref()).unwrap_or_else(|err| {
+            eprintln!("failed to set up trace export: {err}");
+            std::process::exit(1);
+        });
 
     match args.command {
-        Command::Reconcile {
-            requeue_time_secs,
-            ingress_record_creation,
+        Command::Config {
+            command: ConfigCommand::Validate { path },
+        } => match Config::load(&path) {
+            Ok(_) => println!("{} is valid", path.display()),
+            Err(err) => {
+                eprintln!("{}: {err}", path.display());
+                std::process::exit(1);
+            }
+        },
+        Command::Check {
+            path,
+            rrset_ttl,
+            soa_nameserver,
+            soa_mailbox,
         } => {
+            let manifests = check::Manifests::load(&path).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+
+            let soa = SoaDefaults {
+                nameserver: soa_nameserver,
+                mailbox: soa_mailbox,
+            };
+
+            let report = check::check(manifests, rrset_ttl, soa);
+            print!("{report}");
+
+            if report.has_rejected_records() {
+                std::process::exit(1);
+            }
+        }
+        Command::Crds {
+            command: CrdsCommand::Print,
+        } => print!("{}", crds::render().expect("failed to render CRDs")),
+        Command::Crds {
+            command: CrdsCommand::Install,
+        } => block_on(async {
+            let client = Client::try_default().await.unwrap();
+            if let Err(err) = crds::install(client).await {
+                error!("failed to install CRDs: {err}");
+                std::process::exit(1);
+            }
+        }),
+        Command::Crds {
+            command: CrdsCommand::Check,
+        } => block_on(async {
             let client = Client::try_default().await.unwrap();
+            match crds::check(client).await {
+                Ok(differences) if differences.is_empty() => println!("installed CRDs match"),
+                Ok(differences) => {
+                    for difference in differences {
+                        eprintln!("{difference}");
+                    }
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    error!("failed to check CRDs: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }),
+        Command::Explain { command } => block_on(async {
+            let client = Client::try_default().await.unwrap();
+            let default_namespace = client.default_namespace().to_string();
 
-            let futures: FuturesUnordered<Pin<Box<dyn Future<Output = ()>>>> =
-                FuturesUnordered::new();
-
-            futures.push(Box::pin(async {
-                zone::controller(ZoneControllerContext {
-                    client: client.clone(),
-                    requeue_time: Duration::from_secs(requeue_time_secs),
-                })
-                .await;
-            }));
-
-            futures.push(Box::pin(async {
-                record::controller(RecordControllerContext {
-                    client: client.clone(),
-                    requeue_time: Duration::from_secs(requeue_time_secs),
-                })
-                .await;
-            }));
-
-            if ingress_record_creation {
-                futures.push(Box::pin(async {
-                    ingress::controller(IngressControllerContext {
-                        client: client.clone(),
-                        requeue_time: Duration::from_secs(requeue_time_secs),
-                    })
-                    .await;
-                }));
+            let explanation = match &command {
+                ExplainCommand::Record { object } => {
+                    let (namespace, name) = split_object(object, &default_namespace);
+                    explain::record(client, namespace, name).await
+                }
+                ExplainCommand::Zone { object } => {
+                    let (namespace, name) = split_object(object, &default_namespace);
+                    explain::zone(client, namespace, name).await
+                }
+            };
+
+            match explanation {
+                Ok(explanation) => print!("{explanation}"),
+                Err(err) => {
+                    error!("failed to explain adoption: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }),
+        Command::Tree { output } => block_on(async {
+            let client = Client::try_default().await.unwrap();
+
+            match tree::fetch(client).await {
+                Ok(tree) => match output {
+                    Output::Text => print!("{tree}"),
+                    Output::Json => println!(
+                        "{}",
+                        k8s_openapi::serde_json::to_string_pretty(&tree)
+                            .expect("failed to serialize tree")
+                    ),
+                },
+                Err(err) => {
+                    error!("failed to fetch zones and records: {err}");
+                    std::process::exit(1);
+                }
             }
+        }),
+        Command::Reconcile(args) => {
+            let path = args.config.clone();
+            let config = args.into_config().unwrap_or_else(|err| {
+                error!(
+                    "invalid configuration file {}: {err}",
+                    path.unwrap_or_default().display()
+                );
+                std::process::exit(1);
+            });
+
+            let runtime = match config.worker_threads {
+                Some(threads) => tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(threads.get())
+                    .enable_all()
+                    .build(),
+                None => tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build(),
+            }
+            .expect("failed to build async runtime");
+
+            if let Err(err) = runtime.block_on(reconcile(config)) {
+                error!("{err}");
+                drop(telemetry);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Run a one-off command to completion on a single-threaded runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build async runtime")
+        .block_on(future)
+}
+
+/// Split `<namespace>/<name>` into its parts, falling
+/// back to `default_namespace` if none is given.
+fn split_object<'a>(object: &'a str, default_namespace: &'a str) -> (&'a str, &'a str) {
+    object
+        .split_once('/')
+        .unwrap_or((default_namespace, object))
+}
+
+/// Kubernetes controller concurrency setting, where zero means unlimited.
+fn concurrency(limit: Option<NonZeroU16>) -> controller::Config {
+    controller::Config::default().concurrency(limit.map_or(0, NonZeroU16::get))
+}
+
+/// Supervised controller, see [`supervise`].
+type Supervised = Pin<Box<dyn Future<Output = Result<(), Unrecoverable>>>>;
+
+async fn reconcile(config: Config) -> Result<(), Unrecoverable> {
+    let client = Client::try_default().await.unwrap();
+    let requeue_time = Duration::from_secs(config.requeue_time_secs.map_or(30, NonZeroU64::get));
+    let ttl_policy = config.rrset_ttl.unwrap_or_default();
+    let scope = Scope {
+        namespaces: config.scope.namespaces,
+        zone_selector: config.scope.zone_selector,
+        record_selector: config.scope.record_selector,
+        shard: config.scope.shard,
+        dry_run: config.dry_run,
+    };
+
+    let policy = RestartPolicy {
+        max_restarts: config
+            .max_restarts
+            .unwrap_or(RestartPolicy::default().max_restarts),
+        ..Default::default()
+    };
+    let shutdown = Shutdown::on_signal();
+
+    let mut futures: FuturesUnordered<Supervised> = FuturesUnordered::new();
+
+    if config.controllers.zone {
+        let (client, scope, shutdown) = (client.clone(), scope.clone(), shutdown.clone());
+        let soa = config.soa.clone();
+        let controller_config = concurrency(config.concurrency.zone);
+
+        futures.push(Box::pin(supervise(
+            "zone",
+            policy,
+            shutdown.clone(),
+            move || {
+                zone::controller(
+                    ZoneControllerContext {
+                        client: client.clone(),
+                        requeue_time,
+                        backoff: ErrorBackoff::new(BackoffPolicy {
+                            rejected_delay: requeue_time,
+                            ..Default::default()
+                        }),
+                        scope: scope.clone(),
+                        ttl_policy,
+                        soa: soa.clone(),
+                    },
+                    controller_config.clone(),
+                    shutdown.clone(),
+                )
+            },
+        )));
+    }
+
+    if config.controllers.record {
+        let (client, scope, shutdown) = (client.clone(), scope.clone(), shutdown.clone());
+        let controller_config = concurrency(config.concurrency.record);
+
+        futures.push(Box::pin(supervise(
+            "record",
+            policy,
+            shutdown.clone(),
+            move || {
+                record::controller(
+                    RecordControllerContext {
+                        client: client.clone(),
+                        requeue_time,
+                        backoff: ErrorBackoff::new(BackoffPolicy {
+                            rejected_delay: requeue_time,
+                            ..Default::default()
+                        }),
+                        scope: scope.clone(),
+                    },
+                    controller_config.clone(),
+                    shutdown.clone(),
+                )
+            },
+        )));
+    }
+
+    if config.controllers.ingress.unwrap_or(false) {
+        let (client, scope, shutdown) = (client.clone(), scope.clone(), shutdown.clone());
+        let controller_config = concurrency(config.concurrency.ingress);
+        let filter = IngressFilter {
+            classes: config.ingress.classes.clone(),
+            namespaces: config.ingress.namespaces.clone(),
+            selector: config.ingress.selector.clone(),
+        };
 
-            futures::future::select_all(futures.into_iter()).await;
+        futures.push(Box::pin(supervise(
+            "ingress",
+            policy,
+            shutdown.clone(),
+            move || {
+                ingress::controller(
+                    IngressControllerContext {
+                        client: client.clone(),
+                        requeue_time,
+                        backoff: ErrorBackoff::new(BackoffPolicy {
+                            rejected_delay: requeue_time,
+                            ..Default::default()
+                        }),
+                        filter: filter.clone(),
+                        conflicts: Default::default(),
+                        scope: scope.clone(),
+                    },
+                    controller_config.clone(),
+                    shutdown.clone(),
+                )
+            },
+        )));
+    }
+
+    if futures.is_empty() {
+        warn!("all controllers are disabled, exiting");
+        return Ok(());
+    }
+
+    // Once any controller becomes unrecoverable, shut down the
+    // remaining ones gracefully before reporting the failure.
+    let mut outcome = Ok(());
+    while let Some(result) = futures.next().await {
+        if result.is_err() && outcome.is_ok() {
+            shutdown.trigger();
+            outcome = result;
         }
     }
+
+    outcome
 }
@@ -2,160 +2,213 @@ use futures::StreamExt;
 use std::{sync::Arc, time::Duration};
 
 use kube::{
-    api::ListParams,
-    runtime::{controller::Action, watcher, Controller},
-    Api, Client, ResourceExt,
+    runtime::{
+        controller::{self, Action},
+        reflector, Controller, WatchStreamExt,
+    },
+    Client, ResourceExt,
 };
 use kubizone_crds::{
-    kubizone_common::DomainName,
-    v1alpha1::{DomainExt as _, Record, Zone},
+    v1alpha1::{Record, Zone},
     PARENT_ZONE_LABEL,
 };
 use tracing::*;
 
-use crate::{set_fqdn, set_parent};
+use crate::{
+    backoff::ErrorBackoff,
+    children_in, clear_fqdn, clear_normalized_ttl,
+    error::{Error, Step},
+    report_rejections,
+    resolution::{self, Resolution},
+    set_fqdn, set_parent,
+    supervisor::Shutdown,
+    withdraw_publication, Scope,
+};
 
 #[cfg(feature = "dev")]
 const CONTROLLER_NAME: &str = "dev.kubi.zone/record-resolver";
 #[cfg(not(feature = "dev"))]
 const CONTROLLER_NAME: &str = "kubi.zone/record-resolver";
 
-pub async fn controller(context: RecordControllerContext) {
-    let records = Api::<Record>::all(context.client.clone());
-
-    let record_controller = Controller::new(records, watcher::Config::default())
-        .watches(
-            Api::<Zone>::all(context.client.clone()),
-            watcher::Config::default(),
-            kubizone_crds::watch_reference(PARENT_ZONE_LABEL),
-        )
-        .shutdown_on_signal()
-        .run(reconcile_records, record_error_policy, Arc::new(context))
-        .for_each(|res| async move {
-            match res {
-                Ok(o) => info!("reconciled {:?}", o),
-                Err(e) => warn!("reconcile failed: {}", e),
+/// Name of the controller reporting rejected records. Used as a prefix
+/// for Event names, so it cannot contain slashes.
+#[cfg(feature = "dev")]
+const REPORTER_NAME: &str = "dev-kubizone-record-resolver";
+#[cfg(not(feature = "dev"))]
+const REPORTER_NAME: &str = "kubizone-record-resolver";
+
+pub async fn controller(
+    context: RecordControllerContext,
+    config: controller::Config,
+    shutdown: Shutdown,
+) {
+    let context = Arc::new(context);
+    let client = context.client.clone();
+
+    // Records managed by this controller, used for finding the children of changed zones.
+    let (store, writer) = reflector::store();
+    let observed = context.clone();
+    let records = context
+        .scope
+        .watch::<Record>(client.clone(), context.scope.record_config())
+        .inspect(move |event| {
+            if let Ok(event) = event {
+                observed.backoff.observe(event);
             }
-        });
+        })
+        .reflect(writer)
+        .applied_objects();
+
+    let parent = kubizone_crds::watch_reference(PARENT_ZONE_LABEL);
+    let children = store.clone();
+    let zones = context
+        .scope
+        .watch::<Zone>(client, context.scope.zone_config())
+        .touched_objects();
 
-    record_controller.await;
-    warn!("record controller exited");
+    Controller::for_stream(records, store)
+        .watches_stream(zones, move |zone: Zone| {
+            let children = children_in(&children, &zone, |child: &Record| {
+                child.spec.zone_ref.as_ref()
+            });
+            parent(zone).into_iter().chain(children)
+        })
+        .with_config(config)
+        .graceful_shutdown_on(shutdown.wait())
+        .run(reconcile_records, record_error_policy, context.clone())
+        .for_each(|res| {
+            let context = context.clone();
+            async move {
+                match res {
+                    Ok(o) => {
+                        context.backoff.reset(&o.0);
+                        info!("reconciled {:?}", o)
+                    }
+                    Err(e) => warn!("reconcile failed: {}", e),
+                }
+            }
+        })
+        .await;
+
+    debug!("record controller exited");
 }
 
 pub struct RecordControllerContext {
     pub client: Client,
     pub requeue_time: Duration,
+    pub backoff: ErrorBackoff<Record>,
+    pub scope: Scope,
 }
 
-#[tracing::instrument(name = "record", skip_all)]
+#[tracing::instrument(
+    name = "record",
+    skip_all,
+    fields(
+        kind = "Record",
+        namespace = record.metadata.namespace.as_deref(),
+        object = %record.name_any(),
+        fqdn = tracing::field::Empty,
+    )
+)]
 async fn reconcile_records(
     record: Arc<Record>,
     ctx: Arc<RecordControllerContext>,
-) -> Result<Action, kube::Error> {
-    match (record.spec.zone_ref.as_ref(), &record.spec.domain_name) {
-        (Some(zone_ref), DomainName::Partial(partial_domain)) => {
-            // Follow the zoneRef to the supposed parent zone, if it exists
-            // or requeue later if it does not.
-            let Some(parent_zone) = Api::<Zone>::namespaced(
-                ctx.client.clone(),
-                &zone_ref
-                    .namespace
-                    .as_ref()
-                    .or(record.namespace().as_ref())
-                    .cloned()
-                    .unwrap(),
-            )
-            .get_opt(&zone_ref.name)
-            .await?
-            else {
-                warn!("record {record} references unknown zone {zone_ref}");
-                return Ok(Action::requeue(ctx.requeue_time));
-            };
-
-            // If the parent does not have a fully qualified domain name defined
-            // yet, we can't check if the delegations provided by it are valid.
-            // Postpone the reconcilliation until a later time, when the fqdn
-            // has (hopefully) been determined.
-            let Some(parent_fqdn) = parent_zone.fqdn() else {
-                info!("parent zone {parent_zone} missing fqdn, requeuing.",);
-                return Ok(Action::requeue(Duration::from_secs(5)));
-            };
-
-            // This is only "alleged", since we don't know yet if the referenced
-            // zone's delegations allow the adoption.
-            let alleged_fqdn = partial_domain.with_origin(parent_fqdn);
-
-            trace!("record alleged fqdn: {partial_domain} + {parent_fqdn} = {alleged_fqdn}");
-
-            if parent_zone.spec.delegations.iter().any(|delegation| {
-                delegation.covers_namespace(record.namespace().as_deref().unwrap())
-                    && delegation.validate_record(parent_fqdn, record.spec.type_, &alleged_fqdn)
-            }) {
-                set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &record, &alleged_fqdn).await?;
-                set_parent(
-                    CONTROLLER_NAME,
-                    ctx.client.clone(),
-                    &record,
-                    Some(parent_zone.zone_ref()),
-                )
-                .await?;
-            } else {
-                warn!("parent zone {parent_zone} was found, but its delegations does not allow adoption of {record} with {alleged_fqdn} and type {}", record.spec.type_);
-                return Ok(Action::requeue(ctx.requeue_time));
-            }
-        }
-        (None, DomainName::Full(record_fqdn)) => {
-            if set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &record, record_fqdn)
-                .await?
-                .changed()
-            {
-                info!("record {record} fqdn changed to {record_fqdn}, requeuing.");
-                return Ok(Action::requeue(Duration::from_secs(1)));
+) -> Result<Action, Error> {
+    let result = apply_record(record.clone(), &ctx).await;
+
+    report_rejections(
+        result,
+        ctx.client.clone(),
+        REPORTER_NAME,
+        &ctx.backoff,
+        record.as_ref(),
+        ctx.scope.dry_run,
+    )
+    .await
+}
+
+async fn apply_record(record: Arc<Record>, ctx: &RecordControllerContext) -> Result<Action, Error> {
+    let params = ctx.scope.patch_params(CONTROLLER_NAME);
+
+    // Records with a zoneRef only need the referenced zone, while records with a
+    // fully qualified domain name are compared against all zones in scope.
+    let candidates = match record.spec.zone_ref.as_ref() {
+        Some(zone_ref) => {
+            let parent_namespace = zone_ref
+                .namespace
+                .as_ref()
+                .or(record.namespace().as_ref())
+                .cloned()
+                .unwrap();
+
+            // Zones outside of the watched namespaces cannot be followed, since
+            // we neither have permission to read them, nor are notified of changes.
+            if !ctx.scope.covers_namespace(&parent_namespace) {
+                set_parent(&params, ctx.client.clone(), &record, None).await?;
+                withdraw_publication(&params, ctx.client.clone(), &record, None).await?;
+                clear_normalized_ttl(&params, ctx.client.clone(), &record).await?;
+                clear_fqdn(&params, ctx.client.clone(), &record).await?;
+                return Err(Error::rejected(
+                    record.as_ref(),
+                    format!("references zone {zone_ref} in namespace {parent_namespace}, which is outside of the watched namespaces"),
+                ));
             }
 
-            // Fetch all zones from across the cluster and then filter down results to only parent
-            // zones which are valid parent zones for this one.
-            //
-            // This means filtering out parent zones without fqdns, as well as ones which do not
-            // have appropriate delegations for our `zone`'s namespace and suffix.
-            if let Some(longest_parent_zone) = Api::<Zone>::all(ctx.client.clone())
-                .list(&ListParams::default())
-                .await?
+            ctx.scope
+                .get_zone(ctx.client.clone(), &parent_namespace, &zone_ref.name)
+                .await
+                .map_err(|err| Error::api(record.as_ref(), Step::FetchZones, err))?
                 .into_iter()
-                .filter(|parent| {
-                    parent
-                        .fqdn()
-                        .is_some_and(|parent_fqdn| record_fqdn.is_subdomain_of(parent_fqdn))
-                })
-                .max_by_key(|parent| parent.fqdn().unwrap().as_ref().len())
-            {
-                if longest_parent_zone.validate_record(&record) {
-                    set_parent(
-                        CONTROLLER_NAME,
-                        ctx.client.clone(),
-                        &record,
-                        Some(longest_parent_zone.zone_ref()),
-                    )
-                    .await?;
-                } else {
-                    warn!("{longest_parent_zone} is the most immediate parent zone of {record}, but the zone's delegation rules do not allow the adoption of it.");
-                    set_parent(CONTROLLER_NAME, ctx.client.clone(), &record, None).await?;
-                }
-            } else {
-                warn!(
-                    "record {record} ({}) does not fit into any found parent Zone",
-                    &record.spec.domain_name
-                );
-                set_parent(CONTROLLER_NAME, ctx.client.clone(), &record, None).await?;
-            };
+                .collect()
+        }
+        None => ctx
+            .scope
+            .list::<Zone>(ctx.client.clone(), &ctx.scope.zone_params())
+            .await
+            .map_err(|err| Error::api(record.as_ref(), Step::FetchZones, err))?,
+    };
+
+    let candidates = resolution::candidate_zones(candidates);
+
+    let resolution = resolution::resolve_record(&record, &candidates);
+    if let Some(fqdn) = resolution.fqdn() {
+        tracing::Span::current().record("fqdn", tracing::field::display(fqdn));
+    }
+
+    match resolution {
+        Resolution::Adopted { fqdn, parent } => {
+            set_fqdn(&params, ctx.client.clone(), &record, &fqdn).await?;
+            withdraw_publication(&params, ctx.client.clone(), &record, Some(&parent)).await?;
+            set_parent(&params, ctx.client.clone(), &record, Some(parent)).await?;
         }
-        (Some(zone_ref), DomainName::Full(record_fqdn)) => {
-            warn!("record {record} has both a fully qualified domain_name ({record_fqdn}) and a zoneRef({zone_ref}). It cannot have both.");
-            return Ok(Action::requeue(ctx.requeue_time));
+        Resolution::TopLevel { .. } => unreachable!("records are never top level"),
+        Resolution::Unadopted {
+            fqdn: Some(fqdn),
+            reason,
+        } => {
+            set_fqdn(&params, ctx.client.clone(), &record, &fqdn).await?;
+            set_parent(&params, ctx.client.clone(), &record, None).await?;
+            withdraw_publication(&params, ctx.client.clone(), &record, None).await?;
+            clear_normalized_ttl(&params, ctx.client.clone(), &record).await?;
+            return Err(Error::rejected(record.as_ref(), reason));
         }
-        (None, DomainName::Partial(_)) => {
-            warn!("{record} has neither zoneRef nor a fully qualified domainName, making it impossible to deduce its parent zone.");
-            return Ok(Action::requeue(ctx.requeue_time));
+        Resolution::Unadopted { fqdn: None, reason } => {
+            // Records with a zoneRef derive their fqdn from the zone
+            // which no longer adopts them, so it no longer applies.
+            set_parent(&params, ctx.client.clone(), &record, None).await?;
+            withdraw_publication(&params, ctx.client.clone(), &record, None).await?;
+            clear_normalized_ttl(&params, ctx.client.clone(), &record).await?;
+            clear_fqdn(&params, ctx.client.clone(), &record).await?;
+            return Err(Error::rejected(record.as_ref(), reason));
+        }
+        Resolution::Invalid { reason } => {
+            set_parent(&params, ctx.client.clone(), &record, None).await?;
+            withdraw_publication(&params, ctx.client.clone(), &record, None).await?;
+            clear_normalized_ttl(&params, ctx.client.clone(), &record).await?;
+            if record.spec.zone_ref.is_some() {
+                clear_fqdn(&params, ctx.client.clone(), &record).await?;
+            }
+            return Err(Error::invalid(record.as_ref(), reason));
         }
     }
 
@@ -164,12 +217,8 @@ async fn reconcile_records(
 
 fn record_error_policy(
     record: Arc<Record>,
-    error: &kube::Error,
-    _ctx: Arc<RecordControllerContext>,
+    error: &Error,
+    ctx: Arc<RecordControllerContext>,
 ) -> Action {
-    error!(
-        "record {} reconciliation encountered error: {error}",
-        record.name_any()
-    );
-    Action::requeue(Duration::from_secs(60))
+    ctx.backoff.on_error(record.as_ref(), error)
 }
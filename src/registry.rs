@@ -0,0 +1,237 @@
+//! Ownership registry for generated Records.
+//!
+//! Records generated from other sources (such as Ingresses) are annotated
+//! with the [`OWNER_ANNOTATION`], identifying the object they were generated
+//! from. Records without this annotation are considered hand-written, and
+//! always take precedence over generated ones.
+//!
+//! A generated Record for a given name and type is only published, if no
+//! other owner has already claimed it.
+
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use k8s_openapi::api::{core::v1::ObjectReference, networking::v1::Ingress};
+use kube::{
+    runtime::events::{Event, EventType, Recorder, Reporter},
+    Client, Resource, ResourceExt,
+};
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::{DomainExt as _, Record};
+use tracing::*;
+
+#[cfg(feature = "dev")]
+pub const OWNER_ANNOTATION: &str = "dev.kubi.zone/owner";
+#[cfg(not(feature = "dev"))]
+pub const OWNER_ANNOTATION: &str = "kubi.zone/owner";
+
+/// Name of the controller reporting conflicts. Used as a prefix
+/// for Event names, so it cannot contain slashes.
+#[cfg(feature = "dev")]
+const REPORTER_NAME: &str = "dev-kubizone-registry";
+#[cfg(not(feature = "dev"))]
+const REPORTER_NAME: &str = "kubizone-registry";
+
+/// Object which generated a Record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Owner {
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub uid: Option<String>,
+}
+
+impl Owner {
+    /// Produce an Owner identifying the given resource.
+    pub fn of<R>(resource: &R) -> Self
+    where
+        R: Resource,
+        <R as Resource>::DynamicType: Default,
+    {
+        Owner {
+            kind: R::kind(&R::DynamicType::default()).to_string(),
+            namespace: resource.namespace().unwrap_or_default(),
+            name: resource.name_any(),
+            uid: resource.uid(),
+        }
+    }
+
+    /// Serialize the Owner into the format used by the [`OWNER_ANNOTATION`].
+    pub fn as_annotation(&self) -> String {
+        format!("{}/{}/{}", self.kind, self.namespace, self.name)
+    }
+
+    /// Check if the given record was generated by this owner.
+    ///
+    /// Records generated before the introduction of the [`OWNER_ANNOTATION`]
+    /// are recognized by their owner references instead.
+    pub fn owns(&self, record: &Record) -> bool {
+        if let Some(owner) = record.annotations().get(OWNER_ANNOTATION) {
+            return owner == &self.as_annotation();
+        }
+
+        self.uid.as_ref().is_some_and(|uid| {
+            record
+                .owner_references()
+                .iter()
+                .any(|reference| &reference.uid == uid)
+        })
+    }
+}
+
+impl Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}/{}", self.kind, self.namespace, self.name)
+    }
+}
+
+/// Find the record claiming the `fqdn` and `type_` pair, which prevents `owner`
+/// from publishing its own, if any.
+///
+/// Hand-written records always hold the claim, while records generated by other
+/// owners only hold it if they were created before the owner's own record. Records
+/// created within the same second are ordered by their namespace and name, so that
+/// exactly one owner holds the claim.
+pub fn find_conflict<'a, R: Borrow<Record>>(
+    owner: &Owner,
+    fqdn: &FullyQualifiedDomainName,
+    type_: Type,
+    records: &'a [R],
+) -> Option<&'a Record> {
+    let claims = |record: &&Record| {
+        record.spec.type_ == type_
+            && record
+                .fqdn()
+                .or(record.spec.domain_name.as_full())
+                .is_some_and(|record_fqdn| record_fqdn == fqdn)
+    };
+
+    let claim = |record: &Record| {
+        (
+            record.creation_timestamp(),
+            record.namespace(),
+            record.name_any(),
+        )
+    };
+
+    let own_claim = records
+        .iter()
+        .map(Borrow::borrow)
+        .filter(claims)
+        .filter(|record| owner.owns(record))
+        .filter(|record| record.creation_timestamp().is_some())
+        .map(claim)
+        .min();
+
+    records
+        .iter()
+        .map(Borrow::borrow)
+        .filter(claims)
+        .find(|record| {
+            if owner.owns(record) {
+                return false;
+            }
+
+            if !is_generated(record) {
+                return true;
+            }
+
+            match (&own_claim, record.creation_timestamp()) {
+                (Some(own), Some(_)) => claim(record) < *own,
+                _ => true,
+            }
+        })
+}
+
+/// Check if the given record was generated from another object, rather than hand-written.
+///
+/// Records generated before the introduction of the [`OWNER_ANNOTATION`] are recognized
+/// by their owner reference to the Ingress they were generated from. Records owned by
+/// anything else, such as other operators, are considered hand-written.
+pub fn is_generated(record: &Record) -> bool {
+    record.annotations().contains_key(OWNER_ANNOTATION)
+        || record.owner_references().iter().any(|reference| {
+            reference.kind == <Ingress as k8s_openapi::Resource>::KIND
+                && reference.api_version.split('/').next()
+                    == Some(<Ingress as k8s_openapi::Resource>::GROUP)
+        })
+}
+
+/// Keeps track of already reported conflicts, so each conflict only
+/// produces a single set of Events.
+#[derive(Default, Clone)]
+pub struct Conflicts {
+    reported: Arc<Mutex<HashSet<(Owner, FullyQualifiedDomainName, Type)>>>,
+}
+
+impl Conflicts {
+    /// Report a conflict between `owner`'s generated record and the
+    /// `existing` record, by emitting Events on both objects.
+    ///
+    /// Conflicts which have already been reported are ignored.
+    pub async fn report(
+        &self,
+        client: Client,
+        owner: &Owner,
+        owner_ref: ObjectReference,
+        existing: &Record,
+        fqdn: &FullyQualifiedDomainName,
+        type_: Type,
+    ) -> Result<(), kube::Error> {
+        let key = (owner.clone(), fqdn.clone(), type_);
+        if self.reported.lock().unwrap().contains(&key) {
+            return Ok(());
+        }
+
+        warn!("{owner} cannot publish {type_} record for {fqdn}, since it is already claimed by record {existing}");
+
+        Recorder::new(
+            client.clone(),
+            Reporter::from(REPORTER_NAME),
+            owner_ref.clone(),
+        )
+        .publish(Event {
+            type_: EventType::Warning,
+            reason: "RecordConflict".to_string(),
+            note: Some(format!(
+                "{type_} record for {fqdn} is already claimed by record {existing}"
+            )),
+            action: "PublishRecord".to_string(),
+            secondary: Some(existing.object_ref(&())),
+        })
+        .await?;
+
+        Recorder::new(client, Reporter::from(REPORTER_NAME), existing.object_ref(&()))
+            .publish(Event {
+                type_: EventType::Warning,
+                reason: "RecordConflict".to_string(),
+                note: Some(format!(
+                    "{owner} attempted to publish a {type_} record for {fqdn}, which this record already claims"
+                )),
+                action: "PublishRecord".to_string(),
+                secondary: Some(owner_ref),
+            })
+            .await?;
+
+        // Only considered reported once both Events were published, so
+        // that failing to publish either of them is retried.
+        self.reported.lock().unwrap().insert(key);
+        Ok(())
+    }
+
+    /// Forget about a previously reported conflict, once it has been resolved.
+    pub fn resolve(&self, owner: &Owner, fqdn: &FullyQualifiedDomainName, type_: Type) {
+        if self
+            .reported
+            .lock()
+            .unwrap()
+            .remove(&(owner.clone(), fqdn.clone(), type_))
+        {
+            info!("conflict for {owner}'s {type_} record for {fqdn} has been resolved");
+        }
+    }
+}
@@ -0,0 +1,946 @@
+//! Cluster-independent adoption rules.
+//!
+//! Computes the desired fqdns, parents and entries of Zones and Records from
+//! an in-memory view of the cluster. The controllers only fetch the objects
+//! involved and apply the difference between their current and desired state.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
+use k8s_openapi::serde_json;
+use kube::{runtime::reflector::ObjectRef, Resource, ResourceExt};
+use kubizone_common::{Class, DomainName, DomainSegment, FullyQualifiedDomainName, Type};
+use kubizone_crds::{
+    v1alpha1::{DomainExt, Record, RecordStatus, Zone, ZoneEntry, ZoneRef, ZoneStatus},
+    PARENT_ZONE_LABEL,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// Annotation on a Zone, which when set to `true` publishes the entries of
+/// its child zones as part of its own, for providers only hosting a single
+/// zone per domain.
+#[cfg(feature = "dev")]
+pub const FLATTEN_ANNOTATION: &str = "dev.kubi.zone/flatten";
+#[cfg(not(feature = "dev"))]
+pub const FLATTEN_ANNOTATION: &str = "kubi.zone/flatten";
+
+/// Annotation on a Record, written by the zone controller once its content has
+/// been published, reporting the [`Publication`] of its current generation.
+#[cfg(feature = "dev")]
+pub const PUBLISHED_ANNOTATION: &str = "dev.kubi.zone/published";
+#[cfg(not(feature = "dev"))]
+pub const PUBLISHED_ANNOTATION: &str = "kubi.zone/published";
+
+/// Annotation on a Record whose TTL differs from the rest of its RRset, reporting
+/// the TTL it is published with instead. See [`TtlPolicy`].
+#[cfg(feature = "dev")]
+pub const NORMALIZED_TTL_ANNOTATION: &str = "dev.kubi.zone/normalized-ttl";
+#[cfg(not(feature = "dev"))]
+pub const NORMALIZED_TTL_ANNOTATION: &str = "kubi.zone/normalized-ttl";
+
+/// Record types whose semantics are undefined when owned by a wildcard domain
+/// name, and which are therefore never adopted. See [RFC 4592 §4](https://datatracker.ietf.org/doc/html/rfc4592#section-4).
+const WILDCARD_FORBIDDEN_TYPES: [Type; 3] = [Type::SOA, Type::NS, Type::DNAME];
+
+/// Check whether a record of the given type may be owned by `fqdn`.
+///
+/// Some record types are never allowed at a wildcard owner name
+/// (`*.example.org.`), regardless of the delegations of the zone. Whether
+/// those delegations cover the owner name is checked separately, by
+/// [`Zone::validate_record`](kubizone_crds::v1alpha1::Zone::validate_record).
+pub fn allowed_owner(fqdn: &FullyQualifiedDomainName, type_: Type) -> bool {
+    !(fqdn.iter().next().is_some_and(DomainSegment::is_wildcard)
+        && WILDCARD_FORBIDDEN_TYPES.contains(&type_))
+}
+
+/// Outcome of resolving the fqdn and parent of a Zone or Record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Adopted by `parent` under `fqdn`.
+    Adopted {
+        fqdn: FullyQualifiedDomainName,
+        parent: ZoneRef,
+    },
+    /// Zone without any parent zone, which is expected for top level zones.
+    TopLevel { fqdn: FullyQualifiedDomainName },
+    /// Not adopted by any zone. The fqdn is only known if it
+    /// could be determined without the help of a parent.
+    Unadopted {
+        fqdn: Option<FullyQualifiedDomainName>,
+        reason: String,
+    },
+    /// The spec is invalid, so neither fqdn nor parent can be determined.
+    Invalid { reason: String },
+}
+
+impl Resolution {
+    /// Fully qualified domain name, if it could be determined.
+    pub fn fqdn(&self) -> Option<&FullyQualifiedDomainName> {
+        match self {
+            Resolution::Adopted { fqdn, .. } | Resolution::TopLevel { fqdn } => Some(fqdn),
+            Resolution::Unadopted { fqdn, .. } => fqdn.as_ref(),
+            Resolution::Invalid { .. } => None,
+        }
+    }
+}
+
+/// Zone referenced by `zone_ref`, resolving its namespace relative to `resource`.
+pub fn referenced_zone<'a, K: ResourceExt>(
+    resource: &K,
+    zone_ref: &ZoneRef,
+    zones: &'a [Zone],
+) -> Option<&'a Zone> {
+    let namespace = zone_ref
+        .namespace
+        .clone()
+        .or(resource.namespace())
+        .unwrap_or_default();
+
+    zones.iter().find(|zone| {
+        zone.name_any() == zone_ref.name && zone.namespace().as_deref() == Some(&namespace)
+    })
+}
+
+/// Zones forming a cycle, when following the zoneRefs of `zone` through `zones`.
+///
+/// The cycle does not necessarily include `zone` itself, if its zoneRef
+/// merely leads into one. Returns `None` if the chain of zoneRefs ends.
+pub fn zone_ref_cycle<'a>(zone: &'a Zone, zones: &'a [Zone]) -> Option<Vec<&'a Zone>> {
+    let mut chain = vec![zone];
+    let mut current = zone;
+
+    while let Some(zone_ref) = current.spec.zone_ref.as_ref() {
+        let next = referenced_zone(current, zone_ref, zones)?;
+
+        if let Some(start) = chain
+            .iter()
+            .position(|visited| visited.zone_ref() == next.zone_ref())
+        {
+            return Some(chain.split_off(start));
+        }
+
+        chain.push(next);
+        current = next;
+    }
+
+    None
+}
+
+/// Check whether `resource`, whose spec contains the given `zone_ref`,
+/// is either labelled as adopted by `zone`, or refers to it.
+pub fn is_child<K>(resource: &K, zone_ref: Option<&ZoneRef>, zone: &Zone) -> bool
+where
+    K: ResourceExt + DomainExt,
+{
+    resource.parent() == Some(zone.zone_ref())
+        || zone_ref.is_some_and(|zone_ref| {
+            referenced_zone(resource, zone_ref, std::slice::from_ref(zone)).is_some()
+        })
+}
+
+/// Zones among `zones` which may adopt children. Zones being deleted are left
+/// out, since they are about to detach all of their children.
+pub fn candidate_zones(zones: impl IntoIterator<Item = Zone>) -> Vec<Zone> {
+    zones
+        .into_iter()
+        .filter(|zone| zone.meta().deletion_timestamp.is_none())
+        .collect()
+}
+
+/// Most immediate zone `fqdn` is a subdomain of, if any.
+pub fn longest_parent<'a>(fqdn: &FullyQualifiedDomainName, zones: &'a [Zone]) -> Option<&'a Zone> {
+    zones
+        .iter()
+        .filter(|parent| {
+            parent
+                .fqdn()
+                .is_some_and(|parent_fqdn| fqdn.is_subdomain_of(parent_fqdn))
+        })
+        .max_by_key(|parent| parent.fqdn().unwrap().as_ref().len())
+}
+
+/// Resolve the fqdn and parent of `zone`, given the candidate parent `zones`.
+///
+/// Zones with a zoneRef need every zone along their chain of zoneRefs, while
+/// zones with a fully qualified domain name need every potential parent.
+pub fn resolve_zone(zone: &Zone, zones: &[Zone]) -> Resolution {
+    match (zone.spec.zone_ref.as_ref(), &zone.spec.domain_name) {
+        (Some(zone_ref), DomainName::Partial(partial_domain)) => {
+            let Some(parent_zone) = referenced_zone(zone, zone_ref, zones) else {
+                return Resolution::Unadopted {
+                    fqdn: None,
+                    reason: format!("references unknown zone {zone_ref}"),
+                };
+            };
+
+            // Zones in a cycle would either wait for each other's fqdn forever,
+            // or keep growing their fqdns by prepending each other's names.
+            if let Some(cycle) = zone_ref_cycle(zone, zones) {
+                let path = cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|zone| zone.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                return if cycle
+                    .iter()
+                    .any(|member| member.zone_ref() == zone.zone_ref())
+                {
+                    Resolution::Invalid {
+                        reason: format!("zoneRefs form a cycle: {path}"),
+                    }
+                } else {
+                    Resolution::Unadopted {
+                        fqdn: None,
+                        reason: format!(
+                            "references zone {parent_zone}, whose zoneRefs form a cycle: {path}"
+                        ),
+                    }
+                };
+            }
+
+            let parent_fqdn = match referenced_origin(parent_zone) {
+                Ok(parent_fqdn) => parent_fqdn,
+                Err(reason) => return Resolution::Unadopted { fqdn: None, reason },
+            };
+
+            // This is only "alleged", since we don't know yet if the referenced
+            // zone's delegations allow the adoption.
+            let alleged_fqdn = partial_domain.with_origin(parent_fqdn);
+
+            if parent_zone.spec.delegations.iter().any(|delegation| {
+                delegation.covers_namespace(&zone.namespace().unwrap_or_default())
+                    && delegation.validate_zone(parent_fqdn, &alleged_fqdn)
+            }) {
+                Resolution::Adopted {
+                    fqdn: alleged_fqdn,
+                    parent: parent_zone.zone_ref(),
+                }
+            } else {
+                Resolution::Unadopted {
+                    fqdn: None,
+                    reason: format!("delegations of parent zone {parent_zone} do not allow adoption with {alleged_fqdn}"),
+                }
+            }
+        }
+        (None, DomainName::Full(fqdn)) => {
+            // The zone's own fqdn must be known when validating it against its parent.
+            let mut resolved = zone.clone();
+            resolved.status.get_or_insert_with(ZoneStatus::default).fqdn = Some(fqdn.clone());
+
+            match longest_parent(fqdn, zones) {
+                Some(parent) if parent.validate_zone(&resolved) => Resolution::Adopted {
+                    fqdn: fqdn.clone(),
+                    parent: parent.zone_ref(),
+                },
+                Some(parent) => Resolution::Unadopted {
+                    fqdn: Some(fqdn.clone()),
+                    reason: format!(
+                        "most immediate parent zone {parent} does not allow its adoption"
+                    ),
+                },
+                None => Resolution::TopLevel { fqdn: fqdn.clone() },
+            }
+        }
+        (Some(zone_ref), DomainName::Full(fqdn)) => Resolution::Invalid {
+            reason: format!(
+                "has both a fully qualified domainName ({fqdn}) and a zoneRef ({zone_ref})"
+            ),
+        },
+        (None, DomainName::Partial(_)) => Resolution::Invalid {
+            reason: "has neither a zoneRef nor a fully qualified domainName".to_string(),
+        },
+    }
+}
+
+/// Fqdn of the zone referenced by a zoneRef, or the reason it cannot be adopted by it.
+///
+/// Zones referring to a parent themselves only have an fqdn while they are adopted
+/// by it, so their children are detached along with them, instead of waiting for
+/// them to be adopted again.
+fn referenced_origin(parent: &Zone) -> Result<&FullyQualifiedDomainName, String> {
+    let Some(parent_fqdn) = parent.fqdn() else {
+        return Err(format!("parent zone {parent} has no fqdn"));
+    };
+
+    if parent.spec.zone_ref.is_some() && parent.parent().is_none() {
+        return Err(format!("parent zone {parent} has not been adopted"));
+    }
+
+    Ok(parent_fqdn)
+}
+
+/// Resolve the fqdn and parent of `record`, given the candidate parent `zones`.
+pub fn resolve_record(record: &Record, zones: &[Zone]) -> Resolution {
+    match (record.spec.zone_ref.as_ref(), &record.spec.domain_name) {
+        (Some(zone_ref), DomainName::Partial(partial_domain)) => {
+            let Some(parent_zone) = referenced_zone(record, zone_ref, zones) else {
+                return Resolution::Unadopted {
+                    fqdn: None,
+                    reason: format!("references unknown zone {zone_ref}"),
+                };
+            };
+
+            let parent_fqdn = match referenced_origin(parent_zone) {
+                Ok(parent_fqdn) => parent_fqdn,
+                Err(reason) => return Resolution::Unadopted { fqdn: None, reason },
+            };
+
+            // This is only "alleged", since we don't know yet if the referenced
+            // zone's delegations allow the adoption.
+            let alleged_fqdn = partial_domain.with_origin(parent_fqdn);
+
+            if !allowed_owner(&alleged_fqdn, record.spec.type_) {
+                return Resolution::Unadopted {
+                    fqdn: None,
+                    reason: format!(
+                        "wildcard owner name {alleged_fqdn} is not allowed for {} records",
+                        record.spec.type_
+                    ),
+                };
+            }
+
+            if parent_zone.spec.delegations.iter().any(|delegation| {
+                delegation.covers_namespace(&record.namespace().unwrap_or_default())
+                    && delegation.validate_record(parent_fqdn, record.spec.type_, &alleged_fqdn)
+            }) {
+                Resolution::Adopted {
+                    fqdn: alleged_fqdn,
+                    parent: parent_zone.zone_ref(),
+                }
+            } else {
+                Resolution::Unadopted {
+                    fqdn: None,
+                    reason: format!(
+                        "delegations of parent zone {parent_zone} do not allow adoption with {alleged_fqdn} and type {}",
+                        record.spec.type_
+                    ),
+                }
+            }
+        }
+        (None, DomainName::Full(fqdn)) => {
+            if !allowed_owner(fqdn, record.spec.type_) {
+                return Resolution::Unadopted {
+                    fqdn: Some(fqdn.clone()),
+                    reason: format!(
+                        "wildcard owner name {fqdn} is not allowed for {} records",
+                        record.spec.type_
+                    ),
+                };
+            }
+
+            // The record's own fqdn must be known when validating it against its parent.
+            let mut resolved = record.clone();
+            resolved.status = Some(RecordStatus {
+                fqdn: Some(fqdn.clone()),
+            });
+
+            match longest_parent(fqdn, zones) {
+                Some(parent) if parent.validate_record(&resolved) => Resolution::Adopted {
+                    fqdn: fqdn.clone(),
+                    parent: parent.zone_ref(),
+                },
+                Some(parent) => Resolution::Unadopted {
+                    fqdn: Some(fqdn.clone()),
+                    reason: format!(
+                        "most immediate parent zone {parent} does not allow its adoption"
+                    ),
+                },
+                None => Resolution::Unadopted {
+                    fqdn: Some(fqdn.clone()),
+                    reason: "does not fit into any zone".to_string(),
+                },
+            }
+        }
+        (Some(zone_ref), DomainName::Full(fqdn)) => Resolution::Invalid {
+            reason: format!(
+                "has both a fully qualified domainName ({fqdn}) and a zoneRef ({zone_ref})"
+            ),
+        },
+        (None, DomainName::Partial(_)) => Resolution::Invalid {
+            reason: "has neither a zoneRef nor a fully qualified domainName".to_string(),
+        },
+    }
+}
+
+/// Zone or Record which was not adopted, or excluded from its parent's entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub kind: String,
+    pub object: String,
+    pub reason: String,
+}
+
+impl Rejection {
+    pub fn of<K>(resource: &K, reason: impl Into<String>) -> Self
+    where
+        K: Resource<DynamicType = ()> + Display,
+    {
+        Rejection {
+            kind: K::kind(&()).to_string(),
+            object: resource.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}", self.kind, self.object, self.reason)
+    }
+}
+
+/// Check whether `record`, labelled as a child of `zone`, may be published
+/// in its entries, returning its fqdn if so or the reason it was rejected.
+fn admit<'a>(zone: &Zone, record: &'a Record) -> Result<&'a FullyQualifiedDomainName, String> {
+    if !zone.validate_record(record) {
+        return Err(format!(
+            "has {zone} configured as its parent, but the zone does not allow this delegation"
+        ));
+    }
+
+    // Unwrap safe since fqdn presence is checked in validate_record
+    let fqdn = record.fqdn().unwrap();
+
+    if !allowed_owner(fqdn, record.spec.type_) {
+        return Err(format!(
+            "has {zone} configured as its parent, but its wildcard owner name is not allowed for {} records",
+            record.spec.type_
+        ));
+    }
+
+    Ok(fqdn)
+}
+
+/// Records labelled as children of `zone`.
+fn labelled_children<'a>(zone: &Zone, records: &'a [Record]) -> impl Iterator<Item = &'a Record> {
+    let zone_ref = zone.zone_ref();
+    records
+        .iter()
+        .filter(move |record| record.parent().as_ref() == Some(&zone_ref))
+}
+
+/// Policy for choosing a single TTL for all records of an RRset, since
+/// [RFC 2181 §5.2](https://datatracker.ietf.org/doc/html/rfc2181#section-5.2)
+/// does not allow the records of an RRset to have differing TTLs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TtlPolicy {
+    /// Lowest TTL of all records in the RRset.
+    #[default]
+    Minimum,
+    /// Highest TTL of all records in the RRset.
+    Maximum,
+    /// TTL of the record in the RRset which was created first.
+    FirstCreated,
+}
+
+impl TtlPolicy {
+    /// TTL of an RRset published with `current`, once another record with `ttl` joins it.
+    fn merge(self, current: u32, ttl: u32) -> u32 {
+        match self {
+            TtlPolicy::Minimum => current.min(ttl),
+            TtlPolicy::Maximum => current.max(ttl),
+            TtlPolicy::FirstCreated => current,
+        }
+    }
+}
+
+/// Owner name, type and class identifying an RRset.
+type RRset = (FullyQualifiedDomainName, Type, Class);
+
+/// TTL of every RRset among the `records` published in `zone`, chosen according to `policy`.
+fn rrset_ttls(zone: &Zone, records: &[Record], policy: TtlPolicy) -> HashMap<RRset, u32> {
+    let mut records: Vec<_> = labelled_children(zone, records)
+        .filter_map(|record| Some((admit(zone, record).ok()?, record)))
+        .collect();
+
+    // Records without a creation timestamp, such as ones read from
+    // manifests, are considered to be created last, ordered by name.
+    records.sort_by_key(|(_, record)| {
+        (
+            record.metadata.creation_timestamp.is_none(),
+            record.metadata.creation_timestamp.clone(),
+            record.namespace(),
+            record.name_any(),
+        )
+    });
+
+    let mut ttls = HashMap::new();
+    for (fqdn, record) in records {
+        let ttl = record.spec.ttl.unwrap_or(zone.spec.ttl);
+
+        ttls.entry((fqdn.clone(), record.spec.type_, record.spec.class))
+            .and_modify(|current: &mut u32| *current = policy.merge(*current, ttl))
+            .or_insert(ttl);
+    }
+
+    ttls
+}
+
+/// Give all `entries` of an RRset a single TTL chosen according to `policy`,
+/// considering entries listed first to be created first.
+fn normalize_entries(entries: &mut [ZoneEntry], policy: TtlPolicy) {
+    let mut ttls = HashMap::new();
+    for entry in entries.iter() {
+        ttls.entry((entry.fqdn.clone(), entry.type_, entry.class))
+            .and_modify(|current: &mut u32| *current = policy.merge(*current, entry.ttl))
+            .or_insert(entry.ttl);
+    }
+
+    for entry in entries {
+        entry.ttl = ttls[&(entry.fqdn.clone(), entry.type_, entry.class)];
+    }
+}
+
+/// Records published in `zone` whose TTL differs from the one their RRset is
+/// published with among the zone's `entries`, mapped to that TTL. See [`TtlPolicy`].
+pub fn normalized_ttls(
+    zone: &Zone,
+    records: &[Record],
+    entries: &[ZoneEntry],
+) -> HashMap<ObjectRef<Record>, u32> {
+    let ttls: HashMap<RRset, u32> = entries
+        .iter()
+        .map(|entry| ((entry.fqdn.clone(), entry.type_, entry.class), entry.ttl))
+        .collect();
+
+    labelled_children(zone, records)
+        .filter_map(|record| {
+            let fqdn = admit(zone, record).ok()?;
+            let ttl = *ttls.get(&(fqdn.clone(), record.spec.type_, record.spec.class))?;
+
+            (record.spec.ttl.unwrap_or(zone.spec.ttl) != ttl)
+                .then(|| (ObjectRef::from_obj(record), ttl))
+        })
+        .collect()
+}
+
+/// Build the entries of `zone` from the `records` labelled as its children,
+/// excluding records which its delegations do not allow.
+///
+/// All entries of an RRset share the TTL chosen by `policy`.
+///
+/// The SOA record is not included, see [`soa_entry`].
+pub fn zone_entries(
+    zone: &Zone,
+    records: &[Record],
+    policy: TtlPolicy,
+) -> (Vec<ZoneEntry>, Vec<Rejection>) {
+    let ttls = rrset_ttls(zone, records, policy);
+
+    let mut entries = Vec::new();
+    let mut rejections = Vec::new();
+
+    for record in labelled_children(zone, records) {
+        let fqdn = match admit(zone, record) {
+            Ok(fqdn) => fqdn,
+            Err(reason) => {
+                rejections.push(Rejection::of(record, reason));
+                continue;
+            }
+        };
+
+        entries.push(ZoneEntry {
+            fqdn: fqdn.clone(),
+            type_: record.spec.type_,
+            class: record.spec.class,
+            ttl: ttls[&(fqdn.clone(), record.spec.type_, record.spec.class)],
+            rdata: record.spec.rdata.clone(),
+        })
+    }
+
+    (entries, rejections)
+}
+
+/// Records whose content is published in the entries of `zone`, see [`zone_entries`].
+pub fn published_records<'a>(zone: &Zone, records: &'a [Record]) -> Vec<&'a Record> {
+    labelled_children(zone, records)
+        .filter(|record| admit(zone, record).is_ok())
+        .collect()
+}
+
+/// Publication of a Record's content in a zone, as reported by its
+/// [`PUBLISHED_ANNOTATION`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Publication {
+    /// Generation of the record whose content was published.
+    pub observed_generation: Option<i64>,
+    /// Zone which published the record, in the form of a parent zone label.
+    pub zone: String,
+    /// Fully qualified domain name the record was published under.
+    pub fqdn: FullyQualifiedDomainName,
+    /// Serial of the zone in which the record's content was first published.
+    pub serial: u32,
+}
+
+impl Publication {
+    /// Publication currently reported by the record's annotation, if any.
+    pub fn of(record: &Record) -> Option<Self> {
+        serde_json::from_str(record.annotations().get(PUBLISHED_ANNOTATION)?).ok()
+    }
+}
+
+/// Publication of `record`'s current content in `zone` at `serial`, or `None`
+/// if its annotation already reports it.
+///
+/// The serial reported is that of the zone in which the current generation
+/// and fqdn of the record were first published, so unrelated changes to the
+/// zone do not change it.
+///
+/// Records whose fqdn has not yet been resolved from their current spec are
+/// still published under their previous fqdn, so they are not reported until
+/// it has been.
+pub fn publication(record: &Record, zone: &Zone, serial: u32) -> Option<Publication> {
+    let fqdn = record.fqdn()?;
+    if specified_fqdn(record, zone).as_ref() != Some(fqdn) {
+        return None;
+    }
+
+    let next = Publication {
+        observed_generation: record.metadata.generation,
+        zone: zone.zone_ref().as_label(),
+        fqdn: fqdn.clone(),
+        serial,
+    };
+
+    match Publication::of(record) {
+        Some(current)
+            if current.observed_generation == next.observed_generation
+                && current.zone == next.zone
+                && current.fqdn == next.fqdn =>
+        {
+            None
+        }
+        _ => Some(next),
+    }
+}
+
+/// Fqdn the current spec of `record` resolves to, if adopted by `zone`.
+fn specified_fqdn(record: &Record, zone: &Zone) -> Option<FullyQualifiedDomainName> {
+    match (record.spec.zone_ref.as_ref(), &record.spec.domain_name) {
+        (None, DomainName::Full(fqdn)) => Some(fqdn.clone()),
+        (Some(zone_ref), DomainName::Partial(partial_domain)) => {
+            referenced_zone(record, zone_ref, std::slice::from_ref(zone))?;
+            Some(partial_domain.with_origin(zone.fqdn()?))
+        }
+        _ => None,
+    }
+}
+
+/// Hash of the zone's spec and entries, used to detect when its serial must change.
+pub fn zone_hash(zone: &Zone, entries: &[ZoneEntry]) -> String {
+    let mut hasher = DefaultHasher::new();
+    (&zone.spec, entries).hash(&mut hasher);
+    hasher.finish().to_string()
+}
+
+/// Serial of a zone, whose contents are now described by `new_hash`.
+///
+/// The serial is only changed if the hash did, in which case it is based on the
+/// current date as per [RFC 1912 §2.2](https://datatracker.ietf.org/doc/html/rfc1912#section-2.2),
+/// or an increment of the previous serial if that is greater.
+pub fn next_serial(zone: &Zone, new_hash: &str, now: OffsetDateTime) -> u32 {
+    let last_serial = zone.serial().unwrap_or_default();
+
+    if zone.hash() == Some(new_hash) {
+        return last_serial;
+    }
+
+    #[rustfmt::skip]
+    let now_serial
+        = now.year()  as u32 * 1000000
+        + now.month() as u32 * 10000
+        + now.day()   as u32 * 100;
+
+    // If it's a new day, use YYYYMMDD00, otherwise just use the increment
+    // of the old serial.
+    std::cmp::max(now_serial, last_serial + 1)
+}
+
+/// Fields of the SOA record published for every zone, which are not part of the Zone spec.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SoaDefaults {
+    /// Primary nameserver (MNAME) of every zone. Defaults to `ns.<origin>`.
+    pub nameserver: Option<FullyQualifiedDomainName>,
+
+    /// Mailbox of the person responsible for every zone (RNAME), encoded
+    /// as a domain name. Defaults to `noc.<origin>`.
+    pub mailbox: Option<FullyQualifiedDomainName>,
+}
+
+/// SOA record heading the entries of a zone with the given `origin`.
+pub fn soa_entry(
+    zone: &Zone,
+    origin: &FullyQualifiedDomainName,
+    serial: u32,
+    soa: &SoaDefaults,
+) -> ZoneEntry {
+    let spec = &zone.spec;
+
+    let nameserver = match &soa.nameserver {
+        Some(nameserver) => nameserver.to_string(),
+        None => format!("ns.{origin}"),
+    };
+
+    let mailbox = match &soa.mailbox {
+        Some(mailbox) => mailbox.to_string(),
+        None => format!("noc.{origin}"),
+    };
+
+    ZoneEntry {
+        fqdn: origin.clone(),
+        type_: Type::SOA,
+        class: Class::IN,
+        ttl: spec.ttl,
+        rdata: format!(
+            "{nameserver} {mailbox} ({serial} {} {} {} {})",
+            spec.refresh, spec.retry, spec.expire, spec.negative_response_cache
+        ),
+    }
+}
+
+/// Check whether `zone` is annotated with [`FLATTEN_ANNOTATION`], publishing
+/// the entries of its child zones as its own.
+pub fn flattens(zone: &Zone) -> bool {
+    zone.annotations()
+        .get(FLATTEN_ANNOTATION)
+        .is_some_and(|value| value == "true")
+}
+
+/// Descendants of `zone` among `zones`, whose entries are published as part of
+/// `zone` when it [flattens](flattens) its children.
+///
+/// Descendants of children which flatten their own children are not included,
+/// since their entries are already part of the child's.
+pub fn flattened_zones<'a>(zone: &Zone, zones: &'a [Zone]) -> Vec<&'a Zone> {
+    let mut flattened = Vec::new();
+    let mut visited = BTreeSet::from([zone.zone_ref().as_label()]);
+    collect_flattened(zone, zones, &mut visited, &mut flattened);
+    flattened
+}
+
+fn collect_flattened<'a>(
+    zone: &Zone,
+    zones: &'a [Zone],
+    visited: &mut BTreeSet<String>,
+    flattened: &mut Vec<&'a Zone>,
+) {
+    let mut children: Vec<_> = zones
+        .iter()
+        .filter(|child| child.parent().as_ref() == Some(&zone.zone_ref()))
+        .collect();
+    children.sort_by_key(|child| child.zone_ref().as_label());
+
+    for child in children {
+        // Children without an fqdn have no entries to publish, and
+        // parent labels forming a cycle must not be followed forever.
+        if child.fqdn().is_none() || !visited.insert(child.zone_ref().as_label()) {
+            continue;
+        }
+
+        flattened.push(child);
+        if !flattens(child) {
+            collect_flattened(child, zones, visited, flattened);
+        }
+    }
+}
+
+/// Compute the desired status of `zone`, given the `records` labelled as its children,
+/// and all `zones` which might be its descendants, if it flattens its children.
+///
+/// Returns `None` if the zone has no fqdn yet.
+pub fn zone_status(
+    zone: &Zone,
+    records: &[Record],
+    zones: &[Zone],
+    policy: TtlPolicy,
+    soa: &SoaDefaults,
+    now: OffsetDateTime,
+) -> Option<(ZoneStatus, Vec<Rejection>)> {
+    let origin = zone.fqdn()?;
+
+    let (mut entries, rejections) = zone_entries(zone, records, policy);
+
+    if flattens(zone) {
+        let flattened = flattened_zones(zone, zones);
+
+        entries.extend(
+            flattened
+                .iter()
+                .filter_map(|child| child.status.as_ref())
+                .flat_map(|status| status.entries.iter().cloned()),
+        );
+
+        // Flattened zones are no longer zones of their own, so
+        // neither their SOA nor their delegations are published.
+        let apexes: Vec<_> = flattened.iter().filter_map(|child| child.fqdn()).collect();
+        entries.retain(|entry| {
+            !(matches!(entry.type_, Type::SOA | Type::NS) && apexes.contains(&&entry.fqdn))
+        });
+
+        // Descendants may publish records within the same RRsets as this zone, or
+        // as each other, with TTLs of their own. The zone's own records come first,
+        // so their TTL is kept when choosing the first created one.
+        normalize_entries(&mut entries, policy);
+    }
+
+    let hash = zone_hash(zone, &entries);
+    let serial = next_serial(zone, &hash, now);
+
+    entries.insert(0, soa_entry(zone, origin, serial, soa));
+
+    Some((
+        ZoneStatus {
+            entries,
+            fqdn: Some(origin.clone()),
+            hash: Some(hash),
+            serial: Some(serial),
+        },
+        rejections,
+    ))
+}
+
+/// In-memory snapshot of the Zones and Records within a cluster.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub zones: Vec<Zone>,
+    pub records: Vec<Record>,
+    pub ttl_policy: TtlPolicy,
+    pub soa: SoaDefaults,
+}
+
+impl Snapshot {
+    /// Resolve every object within the snapshot, updating their fqdns, parent
+    /// labels and zone statuses to the state the controllers would eventually
+    /// converge on. Returns every object which was not adopted.
+    pub fn resolve(&mut self, now: OffsetDateTime) -> Vec<Rejection> {
+        let mut rejections = Vec::new();
+
+        // Zones depend on the fqdns of their parents, so keep resolving them
+        // until nothing changes anymore, like the controller eventually would.
+        for _ in 0..=self.zones.len() {
+            let mut changed = false;
+
+            for index in 0..self.zones.len() {
+                let resolution = resolve_zone(&self.zones[index], &self.zones);
+                changed |= apply_zone(&mut self.zones[index], resolution);
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        for zone in &self.zones {
+            match resolve_zone(zone, &self.zones) {
+                Resolution::Adopted { .. } | Resolution::TopLevel { .. } => (),
+                Resolution::Unadopted { reason, .. } | Resolution::Invalid { reason } => {
+                    rejections.push(Rejection::of(zone, reason))
+                }
+            }
+        }
+
+        for record in &mut self.records {
+            match resolve_record(record, &self.zones) {
+                Resolution::Adopted { fqdn, parent } => {
+                    set_record_fqdn(record, Some(fqdn));
+                    set_parent(record, Some(parent));
+                }
+                Resolution::TopLevel { .. } => unreachable!("records are never top level"),
+                Resolution::Unadopted { fqdn, reason } => {
+                    // Records with a zoneRef derive their fqdn from the zone
+                    // which no longer adopts them, so it no longer applies.
+                    set_record_fqdn(record, fqdn);
+                    set_parent(record, None);
+                    rejections.push(Rejection::of(&*record, reason));
+                }
+                Resolution::Invalid { reason } => {
+                    if record.spec.zone_ref.is_some() {
+                        set_record_fqdn(record, None);
+                    }
+                    set_parent(record, None);
+                    rejections.push(Rejection::of(&*record, reason));
+                }
+            }
+        }
+
+        // Zones flattening their children include the children's entries,
+        // so the deepest zones' statuses have to be computed first.
+        let mut order: Vec<_> = (0..self.zones.len()).collect();
+        order.sort_by_key(|&index| {
+            std::cmp::Reverse(self.zones[index].fqdn().map(|fqdn| fqdn.iter().count()))
+        });
+
+        for index in order {
+            if let Some((status, rejected)) = zone_status(
+                &self.zones[index],
+                &self.records,
+                &self.zones,
+                self.ttl_policy,
+                &self.soa,
+                now,
+            ) {
+                self.zones[index].status = Some(status);
+                rejections.extend(rejected);
+            }
+        }
+
+        rejections
+    }
+}
+
+/// Apply the resolution to the zone the same way the zone controller does,
+/// returning whether anything changed.
+fn apply_zone(zone: &mut Zone, resolution: Resolution) -> bool {
+    let parent = match &resolution {
+        Resolution::Adopted { parent, .. } => Some(parent.clone()),
+        _ => None,
+    };
+
+    // Zones with a zoneRef derive their fqdn from a parent which no longer
+    // adopts them, or which they can no longer be resolved against.
+    let fqdn = match &resolution {
+        Resolution::Unadopted { fqdn: None, .. } => None,
+        Resolution::Invalid { .. } if zone.spec.zone_ref.is_some() => None,
+        Resolution::Invalid { .. } => zone.fqdn().cloned(),
+        _ => resolution.fqdn().cloned(),
+    };
+
+    let mut changed = false;
+    if fqdn.is_none() && zone.status.is_some() {
+        // Detached zones no longer publish any entries either.
+        zone.status = None;
+        changed = true;
+    } else if zone.fqdn() != fqdn.as_ref() {
+        zone.status.get_or_insert_with(ZoneStatus::default).fqdn = fqdn;
+        changed = true;
+    }
+
+    if zone.parent() != parent {
+        set_parent(zone, parent);
+        changed = true;
+    }
+
+    changed
+}
+
+fn set_record_fqdn(record: &mut Record, fqdn: Option<FullyQualifiedDomainName>) {
+    record.status = Some(RecordStatus { fqdn });
+}
+
+fn set_parent<K: Resource>(resource: &mut K, parent: Option<ZoneRef>) {
+    let labels = resource.meta_mut().labels.get_or_insert_with(BTreeMap::new);
+
+    match parent {
+        Some(parent) => labels.insert(PARENT_ZONE_LABEL.to_string(), parent.as_label()),
+        None => labels.remove(PARENT_ZONE_LABEL),
+    };
+}
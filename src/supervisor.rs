@@ -0,0 +1,202 @@
+//! Supervision of the long-running controllers.
+//!
+//! Controllers are restarted with exponential backoff when they fail or exit
+//! unexpectedly, and are only considered unrecoverable once they have failed
+//! repeatedly without ever running stably in between.
+
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+use tracing::*;
+
+/// Signal for gracefully shutting down controllers, letting them
+/// finish their in-flight reconciles before exiting.
+///
+/// Clones share the same signal. A default-constructed signal only
+/// fires when explicitly [triggered](Shutdown::trigger).
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl Shutdown {
+    /// Shutdown signal which fires on SIGTERM or Ctrl+C. A second signal
+    /// terminates the process immediately, without waiting for the drain.
+    pub fn on_signal() -> Self {
+        let shutdown = Shutdown::default();
+
+        let trigger = shutdown.clone();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            info!("received shutdown signal, draining in-flight reconciles");
+            trigger.trigger();
+
+            wait_for_signal().await;
+            warn!("received second shutdown signal, exiting immediately");
+            std::process::exit(1);
+        });
+
+        shutdown
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Future which completes once the signal has fired.
+    pub fn wait(&self) -> impl Future<Output = ()> + Send + Sync + 'static {
+        let mut receiver = self.sender.subscribe();
+        async move {
+            while !*receiver.borrow_and_update() {
+                if receiver.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("failed to register SIGTERM handler");
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Determines how failed controllers are restarted.
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    /// Delay before the first restart, doubled with every consecutive failure.
+    pub initial_backoff: Duration,
+
+    /// Upper limit for the delay between restarts. Controllers which ran for
+    /// at least this long before failing have their failure count reset.
+    pub max_backoff: Duration,
+
+    /// Number of consecutive restarts after which a failing controller
+    /// is considered unrecoverable.
+    pub max_restarts: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: 5,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restarting a controller after its `failures`th consecutive failure.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1 << failures.saturating_sub(1).min(31))
+            .min(self.max_backoff)
+    }
+}
+
+/// A controller kept failing, and was not restarted again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unrecoverable {
+    pub name: &'static str,
+    pub failures: u32,
+}
+
+impl Display for Unrecoverable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} controller failed {} times in a row, giving up",
+            self.name, self.failures
+        )
+    }
+}
+
+impl std::error::Error for Unrecoverable {}
+
+/// Run the controller produced by `controller`, restarting it according to the
+/// `policy` whenever it panics or exits before `shutdown` has been triggered.
+pub async fn supervise<F, Fut>(
+    name: &'static str,
+    policy: RestartPolicy,
+    shutdown: Shutdown,
+    controller: F,
+) -> Result<(), Unrecoverable>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut failures = 0;
+
+    loop {
+        let started = Instant::now();
+        let outcome = tokio::spawn(controller()).await;
+
+        if shutdown.is_triggered() {
+            info!("{name} controller shut down");
+            return Ok(());
+        }
+
+        match outcome {
+            Ok(()) => warn!("{name} controller exited unexpectedly"),
+            Err(err) => error!("{name} controller failed: {err}"),
+        }
+
+        if started.elapsed() >= policy.max_backoff {
+            failures = 0;
+        }
+
+        failures += 1;
+        if failures > policy.max_restarts {
+            let err = Unrecoverable { name, failures };
+            error!("{err}");
+            return Err(err);
+        }
+
+        let backoff = policy.backoff(failures);
+        warn!(
+            "restarting {name} controller in {backoff:?} ({failures}/{})",
+            policy.max_restarts
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => (),
+            _ = shutdown.wait() => {
+                info!("{name} controller shut down");
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Log output and trace export.
+//!
+//! Logs are written to stdout as either plain text or JSON, filtered through
+//! `RUST_LOG`. Spans can additionally be exported to an OpenTelemetry collector
+//! over OTLP/HTTP, allowing a single reconcile to be followed across all of the
+//! API calls it makes.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Name reported to the trace collector.
+const SERVICE_NAME: &str = "kubizone";
+
+/// Format of log output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, including the fields of all enclosing spans.
+    Json,
+}
+
+/// Installed log and trace output. Pending spans are flushed when dropped.
+#[must_use = "dropping the telemetry guard flushes and stops trace export"]
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("failed to flush traces: {err}");
+            }
+        }
+    }
+}
+
+/// Install the global subscriber, writing logs in the given `format`, and
+/// exporting spans to the OTLP/HTTP collector at `otlp_endpoint`, if any.
+pub fn init(
+    format: LogFormat,
+    otlp_endpoint: Option<&str>,
+) -> Result<Telemetry, ExporterBuildError> {
+    let provider = otlp_endpoint
+        .map(|endpoint| {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+                .build()?;
+
+            Ok::<_, ExporterBuildError>(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+                    .build(),
+            )
+        })
+        .transpose()?;
+
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with((format == LogFormat::Text).then(fmt::layer))
+        .with((format == LogFormat::Json).then(|| {
+            fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+        }))
+        .with(provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+        }))
+        .init();
+
+    Ok(Telemetry { provider })
+}
@@ -0,0 +1,271 @@
+//! Hierarchy of Zones and Records, as linked by their parent zone labels.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use kube::{Api, Client, Resource};
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::{DomainExt, Record, Zone, ZoneRef};
+use serde::Serialize;
+
+use crate::resolution::{resolve_zone, Resolution};
+
+/// Zone along with its adopted child zones and records.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneNode {
+    pub zone: String,
+    pub fqdn: Option<FullyQualifiedDomainName>,
+    pub serial: Option<u32>,
+    pub entries: usize,
+    pub zones: Vec<ZoneNode>,
+    pub records: Vec<RecordNode>,
+}
+
+/// Record adopted by a zone.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordNode {
+    pub record: String,
+    pub fqdn: Option<FullyQualifiedDomainName>,
+    #[serde(rename = "type")]
+    pub type_: Type,
+    pub rdata: String,
+}
+
+/// Zone or Record which is not part of the hierarchy.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Detached {
+    pub kind: String,
+    pub object: String,
+    pub fqdn: Option<FullyQualifiedDomainName>,
+    pub reason: String,
+}
+
+impl Detached {
+    fn of<K>(resource: &K, reason: impl Into<String>) -> Self
+    where
+        K: Resource<DynamicType = ()> + DomainExt + Display,
+    {
+        Detached {
+            kind: K::kind(&()).to_string(),
+            object: resource.to_string(),
+            fqdn: resource.fqdn().cloned(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// All top level zones and their descendants, along with the objects
+/// which are either not adopted, or labelled with a missing parent.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tree {
+    pub zones: Vec<ZoneNode>,
+    pub unadopted: Vec<Detached>,
+    pub orphaned: Vec<Detached>,
+}
+
+/// Build the hierarchy from the parent zone labels of `zones` and `records`.
+pub fn build(zones: &[Zone], records: &[Record]) -> Tree {
+    let mut child_zones: BTreeMap<String, Vec<&Zone>> = BTreeMap::new();
+    let mut child_records: BTreeMap<String, Vec<&Record>> = BTreeMap::new();
+    let mut tree = Tree::default();
+
+    let exists = |parent: &ZoneRef| zones.iter().any(|zone| &zone.zone_ref() == parent);
+
+    for zone in zones {
+        match zone.parent() {
+            Some(parent) if exists(&parent) => {
+                child_zones.entry(parent.as_label()).or_default().push(zone)
+            }
+            Some(parent) => tree.orphaned.push(Detached::of(
+                zone,
+                format!("parent zone {parent} does not exist"),
+            )),
+            None => match resolve_zone(zone, zones) {
+                Resolution::TopLevel { .. } => {
+                    child_zones.entry(String::new()).or_default().push(zone)
+                }
+                Resolution::Unadopted { reason, .. } | Resolution::Invalid { reason } => {
+                    tree.unadopted.push(Detached::of(zone, reason))
+                }
+                Resolution::Adopted { parent, .. } => tree
+                    .unadopted
+                    .push(Detached::of(zone, format!("not yet adopted by {parent}"))),
+            },
+        }
+    }
+
+    for record in records {
+        match record.parent() {
+            Some(parent) if exists(&parent) => child_records
+                .entry(parent.as_label())
+                .or_default()
+                .push(record),
+            Some(parent) => tree.orphaned.push(Detached::of(
+                record,
+                format!("parent zone {parent} does not exist"),
+            )),
+            None => tree
+                .unadopted
+                .push(Detached::of(record, "not adopted by any zone")),
+        }
+    }
+
+    let mut visited = BTreeSet::new();
+    tree.zones = children(String::new(), &child_zones, &child_records, &mut visited);
+
+    // Zones whose parent labels form a cycle are never reached from a top level zone.
+    for zone in zones {
+        if zone.parent().is_some_and(|parent| exists(&parent))
+            && !visited.contains(&zone.zone_ref().as_label())
+        {
+            tree.orphaned
+                .push(Detached::of(zone, "not reachable from any top level zone"));
+        }
+    }
+
+    tree
+}
+
+fn children(
+    parent: String,
+    child_zones: &BTreeMap<String, Vec<&Zone>>,
+    child_records: &BTreeMap<String, Vec<&Record>>,
+    visited: &mut BTreeSet<String>,
+) -> Vec<ZoneNode> {
+    let mut nodes = Vec::new();
+
+    for zone in child_zones.get(&parent).into_iter().flatten() {
+        let label = zone.zone_ref().as_label();
+        if !visited.insert(label.clone()) {
+            continue;
+        }
+
+        let mut records: Vec<_> = child_records
+            .get(&label)
+            .into_iter()
+            .flatten()
+            .map(|record| RecordNode {
+                record: record.to_string(),
+                fqdn: record.fqdn().cloned(),
+                type_: record.spec.type_,
+                rdata: record.spec.rdata.clone(),
+            })
+            .collect();
+        records.sort_by(|a, b| a.fqdn.cmp(&b.fqdn).then_with(|| a.record.cmp(&b.record)));
+
+        nodes.push(ZoneNode {
+            zone: zone.to_string(),
+            fqdn: zone.fqdn().cloned(),
+            serial: zone.serial(),
+            entries: zone
+                .status
+                .as_ref()
+                .map(|status| status.entries.len())
+                .unwrap_or_default(),
+            zones: children(label, child_zones, child_records, visited),
+            records,
+        });
+    }
+
+    nodes.sort_by(|a, b| a.fqdn.cmp(&b.fqdn).then_with(|| a.zone.cmp(&b.zone)));
+    nodes
+}
+
+fn fmt_zone(
+    f: &mut std::fmt::Formatter<'_>,
+    zone: &ZoneNode,
+    prefix: &str,
+    last: Option<bool>,
+) -> std::fmt::Result {
+    let (branch, indent) = match last {
+        None => ("", ""),
+        Some(false) => ("├── ", "│   "),
+        Some(true) => ("└── ", "    "),
+    };
+
+    match &zone.fqdn {
+        Some(fqdn) => write!(f, "{prefix}{branch}{fqdn} ({})", zone.zone)?,
+        None => write!(f, "{prefix}{branch}{}", zone.zone)?,
+    }
+    if let Some(serial) = zone.serial {
+        write!(f, " serial {serial}")?;
+    }
+    writeln!(f, ", {} entries", zone.entries)?;
+
+    let prefix = format!("{prefix}{indent}");
+    let count = zone.zones.len() + zone.records.len();
+
+    for (index, child) in zone.zones.iter().enumerate() {
+        fmt_zone(f, child, &prefix, Some(index + 1 == count))?;
+    }
+
+    for (index, record) in zone.records.iter().enumerate() {
+        let branch = if zone.zones.len() + index + 1 == count {
+            "└── "
+        } else {
+            "├── "
+        };
+
+        write!(f, "{prefix}{branch}{} ", record.type_)?;
+        if let Some(fqdn) = &record.fqdn {
+            write!(f, "{fqdn} ")?;
+        }
+        writeln!(f, "({})", record.record)?;
+    }
+
+    Ok(())
+}
+
+impl Display for Detached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind, self.object)?;
+        if let Some(fqdn) = &self.fqdn {
+            write!(f, " ({fqdn})")?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+impl Display for Tree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for zone in &self.zones {
+            fmt_zone(f, zone, "", None)?;
+        }
+
+        if !self.unadopted.is_empty() {
+            writeln!(f, "unadopted:")?;
+            for detached in &self.unadopted {
+                writeln!(f, "    {detached}")?;
+            }
+        }
+
+        if !self.orphaned.is_empty() {
+            writeln!(f, "orphaned:")?;
+            for detached in &self.orphaned {
+                writeln!(f, "    {detached}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fetch all Zones and Records from the cluster, and build their hierarchy.
+pub async fn fetch(client: Client) -> Result<Tree, kube::Error> {
+    let zones = Api::<Zone>::all(client.clone())
+        .list(&Default::default())
+        .await?
+        .items;
+    let records = Api::<Record>::all(client)
+        .list(&Default::default())
+        .await?
+        .items;
+
+    Ok(build(&zones, &records))
+}
@@ -1,31 +1,45 @@
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-    sync::Arc,
-    time::Duration,
-};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use futures::StreamExt;
-use k8s_openapi::serde_json::json;
+use k8s_openapi::serde_json::{self, json};
 use kube::{
-    api::{ListParams, Patch, PatchParams},
-    runtime::{controller::Action, watcher, Controller},
+    api::{Patch, PatchParams},
+    runtime::{
+        controller::{self, Action},
+        finalizer::{self, finalizer},
+        reflector::{self, ObjectRef},
+        Controller, WatchStreamExt,
+    },
     Api, Client, ResourceExt,
 };
-use kubizone_common::{Class, Type};
+use kubizone_common::FullyQualifiedDomainName;
 use kubizone_crds::{
-    kubizone_common::{DomainName, FullyQualifiedDomainName},
-    v1alpha1::{DomainExt as _, Record, Zone, ZoneEntry, ZoneSpec},
+    v1alpha1::{DomainExt as _, Record, Zone, ZoneStatus},
     PARENT_ZONE_LABEL,
 };
 
 use tracing::log::*;
 
-use crate::{set_fqdn, set_parent};
+use crate::{
+    backoff::ErrorBackoff,
+    children_in, clear_fqdn, clear_normalized_ttl, describe,
+    error::{Error, Step},
+    log_dry_run, report_rejections,
+    resolution::{
+        self, Resolution, SoaDefaults, TtlPolicy, NORMALIZED_TTL_ANNOTATION, PUBLISHED_ANNOTATION,
+    },
+    set_parent,
+    supervisor::Shutdown,
+    withdraw_publication, Scope,
+};
 
 pub struct ZoneControllerContext {
     pub client: Client,
     pub requeue_time: Duration,
+    pub backoff: ErrorBackoff<Zone>,
+    pub scope: Scope,
+    pub ttl_policy: TtlPolicy,
+    pub soa: SoaDefaults,
 }
 
 #[cfg(feature = "dev")]
@@ -33,252 +47,452 @@ const CONTROLLER_NAME: &str = "dev.kubi.zone/zone-resolver";
 #[cfg(not(feature = "dev"))]
 const CONTROLLER_NAME: &str = "kubi.zone/zone-resolver";
 
-pub async fn controller(context: ZoneControllerContext) {
-    let zones = Api::<Zone>::all(context.client.clone());
-
-    let zone_controller = Controller::new(zones.clone(), watcher::Config::default())
-        .watches(
-            Api::<Zone>::all(context.client.clone()),
-            watcher::Config::default(),
-            kubizone_crds::watch_reference(PARENT_ZONE_LABEL),
-        )
-        .watches(
-            Api::<Record>::all(context.client.clone()),
-            watcher::Config::default(),
-            kubizone_crds::watch_reference(PARENT_ZONE_LABEL),
-        )
-        .shutdown_on_signal()
-        .run(reconcile_zones, zone_error_policy, Arc::new(context))
-        .for_each(|res| async move {
-            match res {
-                Ok(o) => info!("reconciled: {:?}", o),
-                Err(e) => warn!("reconciliation failed: {}", e),
+#[cfg(feature = "dev")]
+const FINALIZER: &str = "dev.kubi.zone/detach-children";
+#[cfg(not(feature = "dev"))]
+const FINALIZER: &str = "kubi.zone/detach-children";
+
+/// Name of the controller reporting rejected zones. Used as a prefix
+/// for Event names, so it cannot contain slashes.
+#[cfg(feature = "dev")]
+const REPORTER_NAME: &str = "dev-kubizone-zone-resolver";
+#[cfg(not(feature = "dev"))]
+const REPORTER_NAME: &str = "kubizone-zone-resolver";
+
+pub async fn controller(
+    context: ZoneControllerContext,
+    config: controller::Config,
+    shutdown: Shutdown,
+) {
+    let context = Arc::new(context);
+    let client = context.client.clone();
+
+    // Zones managed by this controller, used for finding the children of changed zones.
+    let (store, writer) = reflector::store();
+    let observed = context.clone();
+    let zones = context
+        .scope
+        .watch::<Zone>(client.clone(), context.scope.zone_config())
+        .inspect(move |event| {
+            if let Ok(event) = event {
+                observed.backoff.observe(event);
+            }
+        })
+        .reflect(writer)
+        .applied_objects();
+
+    // Changes to a zone affect both its parent's entries, and the fqdns of its
+    // children, which then in turn trigger the reconciliation of their own children.
+    let related = context
+        .scope
+        .watch::<Zone>(client.clone(), context.scope.zone_config())
+        .touched_objects();
+
+    let parent = kubizone_crds::watch_reference(PARENT_ZONE_LABEL);
+    let children = store.clone();
+    let records = context
+        .scope
+        .watch::<Record>(client, context.scope.record_config())
+        .touched_objects();
+
+    Controller::for_stream(zones, store)
+        .watches_stream(related, move |zone: Zone| {
+            let children = children_in(&children, &zone, |child: &Zone| {
+                child.spec.zone_ref.as_ref()
+            });
+            parent(zone).into_iter().chain(children)
+        })
+        .watches_stream(records, kubizone_crds::watch_reference(PARENT_ZONE_LABEL))
+        .with_config(config)
+        .graceful_shutdown_on(shutdown.wait())
+        .run(reconcile_zones, zone_error_policy, context.clone())
+        .for_each(|res| {
+            let context = context.clone();
+            async move {
+                match res {
+                    Ok(o) => {
+                        context.backoff.reset(&o.0);
+                        info!("reconciled: {:?}", o)
+                    }
+                    Err(e) => warn!("reconciliation failed: {}", e),
+                }
             }
-        });
+        })
+        .await;
 
-    zone_controller.await;
-    warn!("zone controller exited");
+    debug!("zone controller exited");
 }
 
-#[tracing::instrument(name = "zone", skip_all)]
+#[tracing::instrument(
+    name = "zone",
+    skip_all,
+    fields(
+        kind = "Zone",
+        namespace = zone.metadata.namespace.as_deref(),
+        object = %zone.name_any(),
+        fqdn = tracing::field::Empty,
+    )
+)]
 async fn reconcile_zones(
     zone: Arc<Zone>,
     ctx: Arc<ZoneControllerContext>,
-) -> Result<Action, kube::Error> {
-    match (zone.spec.zone_ref.as_ref(), &zone.spec.domain_name) {
-        (Some(zone_ref), DomainName::Partial(partial_domain)) => {
-            // Follow the zoneRef to the supposed parent zone, if it exists
-            // or requeue later if it does not.
-            let Some(parent_zone) = Api::<Zone>::namespaced(
-                ctx.client.clone(),
-                &zone_ref
-                    .namespace
-                    .as_ref()
-                    .or(zone.namespace().as_ref())
-                    .cloned()
-                    .unwrap(),
-            )
-            .get_opt(&zone_ref.name)
-            .await?
-            else {
-                warn!("zone {zone} references unknown zone {zone_ref}");
-                return Ok(Action::requeue(Duration::from_secs(30)));
-            };
-
-            // If the parent does not have a fully qualified domain name defined
-            // yet, we can't check if the delegations provided by it are valid.
-            // Postpone the reconcilliation until a later time, when the fqdn
-            // has (hopefully) been determined.
-            let Some(parent_fqdn) = parent_zone.fqdn() else {
-                info!(
-                    "parent zone {} missing fqdn, requeuing.",
-                    parent_zone.name_any()
-                );
-                return Ok(Action::requeue(Duration::from_secs(5)));
-            };
-
-            // This is only "alleged", since we don't know yet if the referenced
-            // zone's delegations allow the adoption.
-            let alleged_fqdn: FullyQualifiedDomainName = partial_domain.with_origin(parent_fqdn);
-
-            trace!("zone alleged fqdn: {partial_domain} + {parent_fqdn} = {alleged_fqdn}");
-
-            if parent_zone.spec.delegations.iter().any(|delegation| {
-                delegation.covers_namespace(zone.namespace().as_deref().unwrap())
-                    && delegation.validate_zone(parent_fqdn, &alleged_fqdn)
-            }) {
-                set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &zone, &alleged_fqdn).await?;
-                set_parent(
-                    CONTROLLER_NAME,
-                    ctx.client.clone(),
-                    &zone,
-                    Some(parent_zone.zone_ref()),
-                )
-                .await?;
-            } else {
-                warn!("parent zone {parent_zone} was found, but its delegations do not allow adoption of {zone} with {alleged_fqdn}");
-                return Ok(Action::requeue(ctx.requeue_time));
+) -> Result<Action, Error> {
+    // Adding the finalizer would persist a change, so
+    // dry-runs reconcile zones without one.
+    let result = if ctx.scope.dry_run {
+        apply_zone(zone.clone(), ctx.clone()).await
+    } else {
+        let api = Api::<Zone>::namespaced(ctx.client.clone(), zone.namespace().as_ref().unwrap());
+
+        finalizer(&api, FINALIZER, zone.clone(), |event| async {
+            match event {
+                finalizer::Event::Apply(zone) => apply_zone(zone, ctx.clone()).await,
+                finalizer::Event::Cleanup(zone) => detach_children(zone, ctx.clone()).await,
             }
+        })
+        .await
+        .map_err(|err| match err {
+            finalizer::Error::ApplyFailed(err) | finalizer::Error::CleanupFailed(err) => err,
+            finalizer::Error::AddFinalizer(err) => {
+                Error::api(zone.as_ref(), Step::AddFinalizer, err)
+            }
+            finalizer::Error::RemoveFinalizer(err) => {
+                Error::api(zone.as_ref(), Step::RemoveFinalizer, err)
+            }
+            finalizer::Error::UnnamedObject | finalizer::Error::InvalidFinalizer => {
+                Error::invalid(zone.as_ref(), err.to_string())
+            }
+        })
+    };
+
+    report_rejections(
+        result,
+        ctx.client.clone(),
+        REPORTER_NAME,
+        &ctx.backoff,
+        zone.as_ref(),
+        ctx.scope.dry_run,
+    )
+    .await
+}
+
+async fn apply_zone(zone: Arc<Zone>, ctx: Arc<ZoneControllerContext>) -> Result<Action, Error> {
+    let params = ctx.scope.patch_params(CONTROLLER_NAME);
+
+    // Zones with a zoneRef need the entire chain of zones they refer to, in order
+    // to detect cycles, while zones with a fully qualified domain name are compared
+    // against all zones in scope.
+    if let Some(zone_ref) = zone.spec.zone_ref.as_ref() {
+        let parent_namespace = zone_ref
+            .namespace
+            .as_ref()
+            .or(zone.namespace().as_ref())
+            .cloned()
+            .unwrap();
+
+        // Zones outside of the watched namespaces cannot be followed, since
+        // we neither have permission to read them, nor are notified of changes.
+        if !ctx.scope.covers_namespace(&parent_namespace) {
+            set_parent(&params, ctx.client.clone(), &zone, None).await?;
+            clear_zone_status(&params, ctx.client.clone(), &zone).await?;
+            return Err(Error::rejected(
+                zone.as_ref(),
+                format!("references zone {zone_ref} in namespace {parent_namespace}, which is outside of the watched namespaces"),
+            ));
         }
-        (None, DomainName::Full(fqdn)) => {
-            set_fqdn(CONTROLLER_NAME, ctx.client.clone(), &zone, fqdn).await?;
-
-            // Fetch all zones from across the cluster and then filter down results to only parent
-            // zones which are valid parent zones for this one.
-            //
-            // This means filtering out parent zones without fqdns, as well as ones which do not
-            // have appropriate delegations for our `zone`'s namespace and suffix.
-            if let Some(longest_parent_zone) = Api::<Zone>::all(ctx.client.clone())
-                .list(&ListParams::default())
-                .await?
-                .into_iter()
-                .filter(|parent| {
-                    parent
-                        .fqdn()
-                        .is_some_and(|parent_fqdn| fqdn.is_subdomain_of(parent_fqdn))
-                })
-                .max_by_key(|parent| parent.fqdn().unwrap().as_ref().len())
-            {
-                if longest_parent_zone.validate_zone(&zone) {
-                    set_parent(
-                        CONTROLLER_NAME,
-                        ctx.client.clone(),
-                        &zone,
-                        Some(longest_parent_zone.zone_ref()),
-                    )
-                    .await?;
-                } else {
-                    warn!("{longest_parent_zone} is the most immediate parent zone of {zone}, but the zone's delegation rules do not allow the adoption of it.");
-                }
-            } else {
-                info!(
-                    "zone {} ({}) does not fit into any found parent zone. If this is a top level zone, then this is expected.",
-                    zone.name_any(),
-                    &zone.spec.domain_name
-                );
-            };
+    }
+
+    let candidates = ctx
+        .scope
+        .list::<Zone>(ctx.client.clone(), &ctx.scope.zone_params())
+        .await
+        .map_err(|err| Error::api(zone.as_ref(), Step::FetchZones, err))?;
+
+    let candidates = resolution::candidate_zones(candidates);
+
+    let resolution = resolution::resolve_zone(&zone, &candidates);
+    if let Some(fqdn) = resolution.fqdn() {
+        tracing::Span::current().record("fqdn", tracing::field::display(fqdn));
+    }
+
+    let fqdn = match resolution {
+        Resolution::Adopted { fqdn, parent } => {
+            set_parent(&params, ctx.client.clone(), &zone, Some(parent)).await?;
+            fqdn
         }
-        (Some(zone_ref), DomainName::Full(fqdn)) => {
-            warn!("zone {zone} has both a fully qualified domain_name ({fqdn}) and a zoneRef({zone_ref}). It cannot have both.");
-            return Ok(Action::requeue(ctx.requeue_time));
+        Resolution::TopLevel { fqdn } => {
+            set_parent(&params, ctx.client.clone(), &zone, None).await?;
+            info!("zone {zone} ({fqdn}) does not fit into any found parent zone. If this is a top level zone, then this is expected.");
+            fqdn
         }
-        (None, DomainName::Partial(_)) => {
-            warn!("{zone} has neither zoneRef nor a fully qualified domainName, making it impossible to deduce its parent zone.");
-            return Ok(Action::requeue(ctx.requeue_time));
+        Resolution::Unadopted {
+            fqdn: Some(fqdn),
+            reason,
+        } => {
+            // Unadopted zones can still serve their own records.
+            set_parent(&params, ctx.client.clone(), &zone, None).await?;
+            update_zone_status(zone.clone(), &fqdn, &candidates, &ctx, &params).await?;
+            return Err(Error::rejected(zone.as_ref(), reason));
         }
-    }
+        Resolution::Unadopted { fqdn: None, reason } => {
+            // Zones with a zoneRef derive their fqdn from the parent which no
+            // longer adopts them, so it no longer applies, and neither do the
+            // entries published under it.
+            set_parent(&params, ctx.client.clone(), &zone, None).await?;
+            clear_zone_status(&params, ctx.client.clone(), &zone).await?;
+            return Err(Error::rejected(zone.as_ref(), reason));
+        }
+        Resolution::Invalid { reason } => {
+            set_parent(&params, ctx.client.clone(), &zone, None).await?;
+            if zone.spec.zone_ref.is_some() {
+                clear_zone_status(&params, ctx.client.clone(), &zone).await?;
+            }
+            return Err(Error::invalid(zone.as_ref(), reason));
+        }
+    };
 
-    update_zone_status(zone, ctx.client.clone()).await?;
+    update_zone_status(zone, &fqdn, &candidates, &ctx, &params).await?;
     Ok(Action::requeue(ctx.requeue_time))
 }
 
-async fn update_zone_status(zone: Arc<Zone>, client: Client) -> Result<(), kube::Error> {
-    let Some(origin) = zone.fqdn() else {
+/// Detach all zones and records which were adopted by, or refer to the deleted
+/// `zone`, so they are reconciled against the remaining zones right away.
+///
+/// Zones and records with a zoneRef derive their fqdn from the deleted
+/// zone, so their fqdn is cleared as well.
+async fn detach_children(
+    zone: Arc<Zone>,
+    ctx: Arc<ZoneControllerContext>,
+) -> Result<Action, Error> {
+    let params = ctx.scope.patch_params(CONTROLLER_NAME);
+    info!("zone {zone} is being deleted, detaching its children.");
+
+    let zones = ctx
+        .scope
+        .list::<Zone>(ctx.client.clone(), &ctx.scope.zone_params())
+        .await
+        .map_err(|err| Error::api(zone.as_ref(), Step::FetchZones, err))?;
+
+    for child in zones
+        .into_iter()
+        .filter(|child| resolution::is_child(child, child.spec.zone_ref.as_ref(), &zone))
+    {
+        let child = Arc::new(child);
+        set_parent(&params, ctx.client.clone(), &child, None).await?;
+        if child.spec.zone_ref.is_some() {
+            clear_zone_status(&params, ctx.client.clone(), &child).await?;
+        }
+    }
+
+    let records = ctx
+        .scope
+        .list::<Record>(ctx.client.clone(), &ctx.scope.record_params(None))
+        .await
+        .map_err(|err| Error::api(zone.as_ref(), Step::ListRecords, err))?;
+
+    for child in records
+        .into_iter()
+        .filter(|child| resolution::is_child(child, child.spec.zone_ref.as_ref(), &zone))
+    {
+        let child = Arc::new(child);
+        set_parent(&params, ctx.client.clone(), &child, None).await?;
+        withdraw_publication(&params, ctx.client.clone(), &child, None).await?;
+        clear_normalized_ttl(&params, ctx.client.clone(), &child).await?;
+        if child.spec.zone_ref.is_some() {
+            clear_fqdn(&params, ctx.client.clone(), &child).await?;
+        }
+    }
+
+    Ok(Action::await_change())
+}
+
+/// Remove the fqdn from the zone's status, along with the entries, hash and serial
+/// derived from it, so that a detached zone no longer publishes anything.
+async fn clear_zone_status(
+    params: &PatchParams,
+    client: Client,
+    zone: &Arc<Zone>,
+) -> Result<(), Error> {
+    let Some(status) = zone.status.as_ref() else {
         return Ok(());
     };
 
+    if status.fqdn.is_none()
+        && status.entries.is_empty()
+        && status.hash.is_none()
+        && status.serial.is_none()
+    {
+        return Ok(());
+    }
+
+    info!("clearing status of detached zone {zone}");
+    let patch = Patch::Merge(json!({
+        "status": {
+            "fqdn": null,
+            "entries": null,
+            "hash": null,
+            "serial": null,
+        }
+    }));
+
+    log_dry_run(params, describe(zone.as_ref()), &patch);
+    Api::<Zone>::namespaced(client, zone.namespace().as_ref().unwrap())
+        .patch_status(&zone.name_any(), params, &patch)
+        .await
+        .map_err(|err| Error::api(zone.as_ref(), Step::SetFqdn, err))?;
+
+    Ok(())
+}
+
+/// Write the zone's `fqdn` along with the entries, hash and serial derived from it,
+/// including the entries of its descendants among `zones`, if it flattens them.
+///
+/// Both are written in a single patch, so entries of records which were resolved
+/// under a previous fqdn are withdrawn at the same time as the fqdn changes.
+async fn update_zone_status(
+    zone: Arc<Zone>,
+    fqdn: &FullyQualifiedDomainName,
+    zones: &[Zone],
+    ctx: &ZoneControllerContext,
+    params: &PatchParams,
+) -> Result<(), Error> {
+    let client = ctx.client.clone();
+
+    if zone.fqdn() != Some(fqdn) {
+        info!("updating fqdn for zone {zone} to {fqdn}");
+    }
+
+    let mut resolved = zone.as_ref().clone();
+    resolved.status.get_or_insert_with(ZoneStatus::default).fqdn = Some(fqdn.clone());
+
     // Reference to this zone, which other zones and records will use to refer to it by.
-    let zone_ref = ListParams::default().labels(&format!(
+    let zone_ref = ctx.scope.record_params(Some(&format!(
         "{PARENT_ZONE_LABEL}={}",
         zone.zone_ref().as_label()
-    ));
+    )));
 
-    let mut entries = Vec::new();
+    let records = ctx
+        .scope
+        .list::<Record>(client.clone(), &zone_ref)
+        .await
+        .map_err(|err| Error::api(zone.as_ref(), Step::ListRecords, err))?;
 
-    // Insert all child records into the entries list
-    for record in Api::<Record>::all(client.clone())
-        .list(&zone_ref)
-        .await?
-        .into_iter()
-    {
-        if !zone.validate_record(&record) {
-            warn!("record {record} has {zone} configured as its parent, but the zone does not allow this delegation, action could be malicious.");
+    let Some((status, rejections)) = resolution::zone_status(
+        &resolved,
+        &records,
+        zones,
+        ctx.ttl_policy,
+        &ctx.soa,
+        time::OffsetDateTime::now_utc(),
+    ) else {
+        return Ok(());
+    };
+
+    for rejection in rejections {
+        warn!("{rejection}, action could be malicious.");
+    }
+
+    if zone.hash() != status.hash.as_deref() {
+        info!(
+            "zone {zone}'s hash changed (before: {:?}, now: {:?}), updating serial.",
+            zone.hash(),
+            status.hash
+        );
+    }
+
+    let patch = Patch::Merge(json!({
+        "status": {
+            "fqdn": status.fqdn,
+            "hash": status.hash,
+            "entries": status.entries,
+            "serial": status.serial
+        },
+    }));
+
+    log_dry_run(params, describe(zone.as_ref()), &patch);
+    Api::<Zone>::namespaced(client.clone(), zone.namespace().as_ref().unwrap())
+        .patch_status(&zone.name_any(), params, &patch)
+        .await
+        .map_err(|err| Error::api(zone.as_ref(), Step::UpdateStatus, err))?;
+
+    // Records whose TTL was overridden by the rest of their RRset are flagged, until
+    // they agree with it again.
+    let normalized = resolution::normalized_ttls(&resolved, &records, &status.entries);
+    for record in &records {
+        let ttl = normalized
+            .get(&ObjectRef::from_obj(record))
+            .map(|ttl| ttl.to_string());
+
+        if record.annotations().get(NORMALIZED_TTL_ANNOTATION) == ttl.as_ref() {
             continue;
         }
 
-        entries.push(ZoneEntry {
-            fqdn: record.fqdn().unwrap().clone(), // Unwrap safe since fqdn presence is checked in validate_record
-            type_: record.spec.type_,
-            class: record.spec.class,
-            ttl: record.spec.ttl.unwrap_or(zone.spec.ttl),
-            rdata: record.spec.rdata,
-        })
+        match &ttl {
+            Some(ttl) => warn!(
+                "record {record}'s ttl differs from the rest of its {} RRset in zone {zone}, publishing it with ttl {ttl} instead.",
+                record.spec.type_
+            ),
+            None => info!("record {record}'s ttl agrees with the rest of its RRset again."),
+        }
+
+        let patch = Patch::Merge(json!({
+            "metadata": {
+                "annotations": {
+                    NORMALIZED_TTL_ANNOTATION: ttl
+                },
+            }
+        }));
+
+        log_dry_run(params, describe(record), &patch);
+        Api::<Record>::namespaced(client.clone(), record.namespace().as_ref().unwrap())
+            .patch_metadata(&record.name_any(), params, &patch)
+            .await
+            .map_err(|err| Error::api(record, Step::FlagTtl, err))?;
     }
 
-    let mut hasher = DefaultHasher::new();
-    (&zone.spec, &entries).hash(&mut hasher);
-    let new_hash = hasher.finish().to_string();
-
-    let current_hash = zone.status.as_ref().and_then(|status| status.hash.as_ref());
-
-    let last_serial = zone
-        .status
-        .as_ref()
-        .and_then(|status| status.serial)
-        .unwrap_or_default();
-
-    // If the hash changed, we need to update the serial.
-    let serial = if current_hash != Some(&new_hash) {
-        info!("zone {zone}'s hash changed (before: {current_hash:?}, now: {new_hash}), updating serial.");
-        // Compute a serial based on the current datetime in UTC as per:
-        // https://datatracker.ietf.org/doc/html/rfc1912#section-2.2
-        let now = time::OffsetDateTime::now_utc();
-        #[rustfmt::skip]
-        let now_serial
-            = now.year()  as u32 * 1000000
-            + now.month() as u32 * 10000
-            + now.day()   as u32 * 100;
-
-        // If it's a new day, use YYYYMMDD00, otherwise just use the increment
-        // of the old serial.
-        std::cmp::max(now_serial, last_serial + 1)
-    } else {
-        last_serial
-    };
+    // Only once the entries have been written may the records be reported as published,
+    // while records whose entries the zone rejected are no longer published by it.
+    let published = resolution::published_records(&resolved, &records);
+    let published_refs: HashSet<_> = published
+        .iter()
+        .map(|record| ObjectRef::from_obj(*record))
+        .collect();
+    for record in records
+        .iter()
+        .filter(|record| !published_refs.contains(&ObjectRef::from_obj(*record)))
+    {
+        withdraw_publication(params, client.clone(), record, None).await?;
+    }
 
-    // Insert a SOA record at the beginning of the entry list.
-    let ZoneSpec {
-        ttl,
-        refresh,
-        retry,
-        expire,
-        negative_response_cache,
-        ..
-    } = zone.spec;
-
-    entries.insert(0, ZoneEntry {
-        fqdn: origin.clone(),
-        type_: Type::SOA,
-        class: Class::IN,
-        ttl,
-        rdata: format!("ns.{origin} noc.{origin} ({serial} {refresh} {retry} {expire} {negative_response_cache})"),
-    });
+    let serial = status.serial.unwrap_or_default();
+    for record in published {
+        let Some(publication) = resolution::publication(record, &resolved, serial) else {
+            continue;
+        };
 
-    Api::<Zone>::namespaced(client, zone.namespace().as_ref().unwrap())
-        .patch_status(
-            &zone.name_any(),
-            &PatchParams::apply(CONTROLLER_NAME),
-            &Patch::Merge(json!({
-                "status": {
-                    "hash": new_hash,
-                    "entries": entries,
-                    "serial": Some(serial)
+        info!(
+            "record {record} published by zone {zone} at serial {}",
+            publication.serial
+        );
+
+        let patch = Patch::Merge(json!({
+            "metadata": {
+                "annotations": {
+                    PUBLISHED_ANNOTATION: serde_json::to_string(&publication).unwrap()
                 },
-            })),
-        )
-        .await?;
+            }
+        }));
+
+        log_dry_run(params, describe(record), &patch);
+        Api::<Record>::namespaced(client.clone(), record.namespace().as_ref().unwrap())
+            .patch_metadata(&record.name_any(), params, &patch)
+            .await
+            .map_err(|err| Error::api(record, Step::MarkPublished, err))?;
+    }
 
     Ok(())
 }
 
-fn zone_error_policy(
-    zone: Arc<Zone>,
-    error: &kube::Error,
-    _ctx: Arc<ZoneControllerContext>,
-) -> Action {
-    error!(
-        "zone {} reconciliation encountered error: {error}",
-        zone.name_any()
-    );
-    Action::requeue(Duration::from_secs(60))
+fn zone_error_policy(zone: Arc<Zone>, error: &Error, ctx: Arc<ZoneControllerContext>) -> Action {
+    ctx.backoff.on_error(zone.as_ref(), error)
 }
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use k8s_openapi::serde_json::{self, json};
+use kube::{
+    core::ErrorResponse,
+    runtime::{reflector::ObjectRef, watcher::Event},
+};
+use kubizone::{
+    backoff::{jitter, BackoffPolicy, ErrorBackoff, ErrorClass},
+    error::{Error, Step},
+};
+use kubizone_crds::v1alpha1::Zone;
+
+fn api_error(code: u16) -> kube::Error {
+    kube::Error::Api(ErrorResponse {
+        status: "Failure".to_string(),
+        message: String::new(),
+        reason: String::new(),
+        code,
+    })
+}
+
+fn zone_error(zone: &Zone, code: u16) -> Error {
+    Error::api(zone, Step::SetFqdn, api_error(code))
+}
+
+fn zone(name: &str) -> Zone {
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Zone",
+        "metadata": { "name": name, "namespace": "default" },
+        "spec": { "domainName": "example.org.", "delegations": [] },
+    }))
+    .unwrap()
+}
+
+#[test]
+fn errors_are_classified() {
+    assert_eq!(ErrorClass::of_api(&api_error(409)), ErrorClass::Conflict);
+    assert_eq!(ErrorClass::of_api(&api_error(404)), ErrorClass::NotFound);
+    assert_eq!(ErrorClass::of_api(&api_error(429)), ErrorClass::Throttled);
+    assert_eq!(ErrorClass::of_api(&api_error(503)), ErrorClass::Server);
+    assert_eq!(ErrorClass::of_api(&api_error(403)), ErrorClass::Rejected);
+    assert_eq!(
+        ErrorClass::of_api(&kube::Error::LinesCodecMaxLineLengthExceeded),
+        ErrorClass::Other
+    );
+
+    let zone = zone("example-org");
+    assert_eq!(
+        ErrorClass::of(&zone_error(&zone, 409)),
+        ErrorClass::Conflict
+    );
+    assert_eq!(
+        ErrorClass::of(&Error::invalid(
+            &zone,
+            "has neither a zoneRef nor a fully qualified domainName"
+        )),
+        ErrorClass::Invalid
+    );
+    assert_eq!(
+        ErrorClass::of(&Error::rejected(&zone, "references unknown zone")),
+        ErrorClass::Unadopted
+    );
+}
+
+#[test]
+fn backoff_doubles_up_to_limit() {
+    let policy = BackoffPolicy::default();
+
+    assert_eq!(
+        policy.backoff(ErrorClass::Server, 1),
+        Duration::from_secs(1)
+    );
+    assert_eq!(
+        policy.backoff(ErrorClass::Server, 2),
+        Duration::from_secs(2)
+    );
+    assert_eq!(
+        policy.backoff(ErrorClass::Server, 5),
+        Duration::from_secs(16)
+    );
+    assert_eq!(policy.backoff(ErrorClass::Server, 100), policy.max_backoff);
+
+    // Conflicts are retried quickly, no matter how often the object failed before.
+    assert_eq!(
+        policy.backoff(ErrorClass::Conflict, 10),
+        policy.conflict_delay
+    );
+
+    // Invalid and unadopted objects are retried at a fixed interval.
+    assert_eq!(
+        policy.backoff(ErrorClass::Unadopted, 10),
+        policy.rejected_delay
+    );
+
+    // Throttling backs off by at least the throttled delay.
+    assert_eq!(
+        policy.backoff(ErrorClass::Throttled, 1),
+        policy.throttled_backoff
+    );
+    assert_eq!(
+        policy.backoff(ErrorClass::Throttled, 6),
+        Duration::from_secs(32)
+    );
+}
+
+#[test]
+fn jitter_shortens_delay_by_at_most_half() {
+    for _ in 0..100 {
+        let delay = jitter(Duration::from_secs(10));
+        assert!(delay >= Duration::from_secs(5));
+        assert!(delay <= Duration::from_secs(10));
+    }
+}
+
+#[test]
+fn failures_are_counted_per_object() {
+    let backoff = ErrorBackoff::<Zone>::default();
+    let (first, second) = (zone("first"), zone("second"));
+    let (first_ref, second_ref) = (ObjectRef::from_obj(&first), ObjectRef::from_obj(&second));
+
+    backoff.on_error(&first, &zone_error(&first, 500));
+    backoff.on_error(&first, &zone_error(&first, 500));
+    backoff.on_error(&second, &zone_error(&second, 500));
+    assert_eq!(backoff.failures(&first_ref), 2);
+    assert_eq!(backoff.failures(&second_ref), 1);
+
+    // Neither conflicts nor rejections count as failures.
+    backoff.on_error(&first, &zone_error(&first, 409));
+    backoff.on_error(&first, &Error::rejected(&first, "references unknown zone"));
+    assert_eq!(backoff.failures(&first_ref), 2);
+
+    backoff.reset(&first_ref);
+    assert_eq!(backoff.failures(&first_ref), 0);
+    assert_eq!(backoff.failures(&second_ref), 1);
+}
+
+#[test]
+fn failures_of_deleted_objects_are_forgotten() {
+    let backoff = ErrorBackoff::<Zone>::default();
+    let (first, second, third) = (zone("first"), zone("second"), zone("third"));
+
+    for zone in [&first, &second, &third] {
+        backoff.on_error(zone, &zone_error(zone, 500));
+    }
+
+    backoff.observe(&Event::Delete(first.clone()));
+    assert_eq!(backoff.failures(&ObjectRef::from_obj(&first)), 0);
+
+    // The second zone was deleted while the watch was restarting.
+    backoff.observe(&Event::Init);
+    backoff.observe(&Event::InitApply(third.clone()));
+    assert_eq!(backoff.failures(&ObjectRef::from_obj(&second)), 1);
+    backoff.observe(&Event::InitDone);
+
+    assert_eq!(backoff.failures(&ObjectRef::from_obj(&second)), 0);
+    assert_eq!(backoff.failures(&ObjectRef::from_obj(&third)), 1);
+}
+
+#[test]
+fn rejections_are_reported_once_per_reason() {
+    let backoff = ErrorBackoff::<Zone>::default();
+    let (first, second) = (zone("first"), zone("second"));
+    let (first, second) = (ObjectRef::from_obj(&first), ObjectRef::from_obj(&second));
+
+    assert!(!backoff.is_reported(&first, "references unknown zone other"));
+    backoff.mark_reported(first.clone(), "references unknown zone other".to_string());
+    backoff.mark_reported(second.clone(), "references unknown zone other".to_string());
+
+    assert!(backoff.is_reported(&first, "references unknown zone other"));
+    assert!(!backoff.is_reported(&first, "references unknown zone another"));
+
+    // Once accepted, the same rejection is reported again.
+    backoff.accepted(&first);
+    assert!(!backoff.is_reported(&first, "references unknown zone other"));
+
+    backoff.observe(&Event::Delete(zone("second")));
+    assert!(!backoff.is_reported(&second, "references unknown zone other"));
+}
+
+#[test]
+fn errors_describe_object_and_step() {
+    let zone = zone("example-org");
+
+    assert!(zone_error(&zone, 500)
+        .to_string()
+        .starts_with("Zone default/example-org: setting fqdn failed: "));
+    assert_eq!(
+        Error::rejected(&zone, "references unknown zone other").to_string(),
+        "Zone default/example-org was not adopted: references unknown zone other"
+    );
+    assert_eq!(Error::invalid(&zone, "").reason(), "InvalidSpec");
+}
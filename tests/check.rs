@@ -0,0 +1,229 @@
+use indoc::formatdoc;
+use kube::Resource;
+use kubizone::{
+    check::{check, Manifests, Report},
+    resolution::{SoaDefaults, TtlPolicy},
+};
+use kubizone_crds::v1alpha1::Zone;
+
+fn resolve(manifests: &str) -> Report {
+    let mut loaded = Manifests::default();
+    loaded.parse(manifests).unwrap();
+    check(loaded, TtlPolicy::default(), SoaDefaults::default())
+}
+
+fn entries(report: &Report, zone: &str) -> Vec<String> {
+    report
+        .zones
+        .iter()
+        .find(|report| report.zone == zone)
+        .unwrap()
+        .entries
+        .iter()
+        .map(|entry| format!("{} {}", entry.fqdn, entry.type_))
+        .collect()
+}
+
+#[test]
+fn longest_matching_zone_adopts() {
+    let api_version = Zone::api_version(&());
+
+    let report = resolve(&formatdoc! {r#"
+        apiVersion: {api_version}
+        kind: Zone
+        metadata:
+          name: example-org
+        spec:
+          domainName: example.org.
+          delegations:
+            - records:
+                - pattern: "*"
+            - zones: ["sub"]
+        ---
+        apiVersion: {api_version}
+        kind: Zone
+        metadata:
+          name: sub-example-org
+        spec:
+          domainName: sub.example.org.
+          delegations:
+            - records:
+                - pattern: "*"
+        ---
+        apiVersion: {api_version}
+        kind: Record
+        metadata:
+          name: www-sub-example-org
+        spec:
+          domainName: www.sub.example.org.
+          type: A
+          rdata: "192.168.0.2"
+    "#});
+
+    assert!(report.rejections.is_empty(), "{report}");
+    assert_eq!(
+        entries(&report, "default/example-org"),
+        ["example.org. SOA"]
+    );
+    assert_eq!(
+        entries(&report, "default/sub-example-org"),
+        ["sub.example.org. SOA", "www.sub.example.org. A"]
+    );
+
+    let sub = report
+        .zones
+        .iter()
+        .find(|zone| zone.zone == "default/sub-example-org")
+        .unwrap();
+    assert_eq!(
+        sub.parent.as_ref().unwrap().as_label(),
+        "example-org.default"
+    );
+}
+
+#[test]
+fn zone_refs_are_followed() {
+    let api_version = Zone::api_version(&());
+
+    // The child zone is listed before its parent, so resolving
+    // it requires the parent to have been resolved first.
+    let report = resolve(&formatdoc! {r#"
+        apiVersion: {api_version}
+        kind: Record
+        metadata:
+          name: www
+          namespace: team
+        spec:
+          domainName: www
+          zoneRef:
+            name: team
+          type: A
+          rdata: "192.168.0.2"
+        ---
+        apiVersion: {api_version}
+        kind: Zone
+        metadata:
+          name: team
+          namespace: team
+        spec:
+          domainName: team
+          zoneRef:
+            name: example-org
+            namespace: default
+          delegations:
+            - records:
+                - pattern: "*"
+        ---
+        apiVersion: {api_version}
+        kind: Zone
+        metadata:
+          name: example-org
+        spec:
+          domainName: example.org.
+          delegations:
+            - namespaces: [team]
+              zones: ["team"]
+    "#});
+
+    assert!(report.rejections.is_empty(), "{report}");
+    assert_eq!(
+        entries(&report, "team/team"),
+        ["team.example.org. SOA", "www.team.example.org. A"]
+    );
+}
+
+#[test]
+fn undelegated_records_are_rejected() {
+    let api_version = Zone::api_version(&());
+
+    let report = resolve(&formatdoc! {r#"
+        apiVersion: {api_version}
+        kind: Zone
+        metadata:
+          name: example-org
+        spec:
+          domainName: example.org.
+          delegations:
+            - records:
+                - pattern: "www"
+                  types: [A]
+        ---
+        apiVersion: {api_version}
+        kind: Record
+        metadata:
+          name: www-example-org
+        spec:
+          domainName: www.example.org.
+          type: A
+          rdata: "192.168.0.2"
+        ---
+        apiVersion: {api_version}
+        kind: Record
+        metadata:
+          name: mail-example-org
+        spec:
+          domainName: www.example.org.
+          type: MX
+          rdata: "10 mail.example.org."
+        ---
+        apiVersion: v1
+        kind: ConfigMap
+        metadata:
+          name: ignored
+    "#});
+
+    assert!(report.has_rejected_records(), "{report}");
+    assert_eq!(report.rejections.len(), 1, "{report}");
+    assert_eq!(report.rejections[0].object, "default/mail-example-org");
+    assert_eq!(
+        entries(&report, "default/example-org"),
+        ["example.org. SOA", "www.example.org. A"]
+    );
+}
+
+#[test]
+fn exported_state_is_stripped() {
+    let api_version = Zone::api_version(&());
+
+    let mut manifests = Manifests::default();
+    manifests
+        .parse(&formatdoc! {r#"
+            apiVersion: {api_version}
+            kind: Zone
+            metadata:
+              name: example-org
+            spec:
+              domainName: example.org.
+              delegations: []
+            status:
+              fqdn: old.example.org.
+              serial: 2024010100
+              hash: stale
+              entries: []
+            ---
+            apiVersion: {api_version}
+            kind: Record
+            metadata:
+              name: www-example-org
+            spec:
+              domainName: www.example.org.
+              type: A
+              rdata: "192.168.0.2"
+            status:
+              fqdn: www.old.example.org.
+        "#})
+        .unwrap();
+
+    assert!(manifests.zones[0].status.is_none());
+    assert!(manifests.records[0].status.is_none());
+
+    let report = check(manifests, TtlPolicy::default(), SoaDefaults::default());
+    assert_eq!(
+        report.zones[0]
+            .fqdn
+            .as_ref()
+            .map(ToString::to_string)
+            .as_deref(),
+        Some("example.org.")
+    );
+}
@@ -2,21 +2,30 @@ use std::{sync::Arc, time::Duration};
 
 use futures::Future;
 use k8s_openapi::{
-    api::core::v1::Namespace,
+    api::{core::v1::Namespace, networking::v1::Ingress},
     apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
-    serde::de::DeserializeOwned, NamespaceResourceScope,
+    serde::de::DeserializeOwned,
+    serde_json::{self, json},
+    NamespaceResourceScope,
 };
 use kube::{
-    api::{DeleteParams, ObjectMeta, PostParams},
+    api::{DeleteParams, ObjectMeta, Patch, PatchParams, PostParams},
     runtime::{
         conditions,
         wait::{await_condition, Condition},
     },
     Api, Client, CustomResourceExt, Resource, ResourceExt,
 };
-use kubizone::{record::RecordControllerContext, zone::ZoneControllerContext};
-use kubizone_common::{DomainName, Type};
-use kubizone_crds::v1alpha1::{Delegation, DomainExt, Record, RecordSpec, Zone, ZoneSpec};
+use kubizone::{
+    backoff::{BackoffPolicy, ErrorBackoff},
+    ingress::{record_name, IngressControllerContext, IngressFilter, RECORDS_ANNOTATION},
+    record::RecordControllerContext,
+    resolution::Publication,
+    zone::ZoneControllerContext,
+    Scope,
+};
+use kubizone_common::{DomainName, FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::{Delegation, DomainExt, Record, RecordSpec, Zone, ZoneRef, ZoneSpec};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info};
 
@@ -133,6 +142,144 @@ impl Context {
         Ok(zone)
     }
 
+    /// Create a zone with a partial domain name, referring to `parent` through its zoneRef.
+    pub async fn zone_with_ref(
+        &self,
+        namespace: &str,
+        name: &str,
+        domain_name: &str,
+        parent: &Zone,
+        delegations: &[Delegation],
+    ) -> Result<Zone, kube::Error> {
+        let api = Api::<Zone>::namespaced(self.inner.read().await.client.clone(), namespace);
+
+        let zone = api
+            .create(
+                &PostParams::default(),
+                &Zone {
+                    metadata: ObjectMeta {
+                        name: Some(name.to_string()),
+                        ..Default::default()
+                    },
+                    spec: ZoneSpec {
+                        domain_name: DomainName::try_from(domain_name.to_string()).unwrap(),
+                        zone_ref: Some(ZoneRef {
+                            name: parent.name_any(),
+                            namespace: parent.namespace(),
+                        }),
+                        delegations: delegations.to_vec(),
+                        ..Default::default()
+                    },
+                    status: None,
+                },
+            )
+            .await?;
+
+        self.inner.write().await.zones.push(zone.clone());
+        Ok(zone)
+    }
+
+    /// Create an A record with a partial domain name, referring to `parent` through its zoneRef.
+    pub async fn a_record_with_ref(
+        &self,
+        namespace: &str,
+        name: &str,
+        domain_name: &str,
+        parent: &Zone,
+    ) -> Result<Record, kube::Error> {
+        let api = Api::<Record>::namespaced(self.inner.read().await.client.clone(), namespace);
+
+        let record = api
+            .create(
+                &PostParams::default(),
+                &Record {
+                    metadata: ObjectMeta {
+                        name: Some(name.to_string()),
+                        ..Default::default()
+                    },
+                    spec: RecordSpec {
+                        domain_name: DomainName::try_from(domain_name).unwrap(),
+                        zone_ref: Some(ZoneRef {
+                            name: parent.name_any(),
+                            namespace: parent.namespace(),
+                        }),
+                        type_: Type::A,
+                        class: kubizone_common::Class::IN,
+                        ttl: None,
+                        rdata: "127.0.0.1".to_string(),
+                    },
+                    status: None,
+                },
+            )
+            .await?;
+
+        self.inner.write().await.records.push(record.clone());
+        Ok(record)
+    }
+
+    /// Set the label `key` to `value` on the given resource.
+    pub async fn label<R>(&self, resource: &R, key: &str, value: &str) -> Result<R, kube::Error>
+    where
+        R: Resource<Scope = NamespaceResourceScope> + Clone + std::fmt::Debug + DeserializeOwned,
+        <R as Resource>::DynamicType: Default,
+    {
+        let api = Api::<R>::namespaced(
+            self.inner.read().await.client.clone(),
+            resource.meta().namespace.as_ref().unwrap(),
+        );
+
+        api.patch(
+            &resource.name_any(),
+            &PatchParams::default(),
+            &Patch::Merge(json!({
+                "metadata": { "labels": { key: value } }
+            })),
+        )
+        .await
+    }
+
+    /// Create an Ingress for the given hosts, and report `address` as its load balancer.
+    pub async fn ingress(
+        &self,
+        namespace: &str,
+        name: &str,
+        hosts: &[&str],
+        address: &str,
+    ) -> Result<Ingress, kube::Error> {
+        let api = Api::<Ingress>::namespaced(self.inner.read().await.client.clone(), namespace);
+
+        let ingress: Ingress = serde_json::from_value(json!({
+            "apiVersion": "networking.k8s.io/v1",
+            "kind": "Ingress",
+            "metadata": { "name": name },
+            "spec": {
+                "rules": hosts.iter().map(|host| json!({
+                    "host": host,
+                    "http": {
+                        "paths": [{
+                            "path": "/",
+                            "pathType": "Prefix",
+                            "backend": { "service": { "name": name, "port": { "number": 80 } } },
+                        }],
+                    },
+                })).collect::<Vec<_>>(),
+            },
+        }))
+        .unwrap();
+
+        api.create(&PostParams::default(), &ingress).await?;
+
+        // There is no ingress controller to assign an address, so report one ourselves.
+        api.patch_status(
+            name,
+            &PatchParams::default(),
+            &Patch::Merge(json!({
+                "status": { "loadBalancer": { "ingress": [{ "ip": address }] } }
+            })),
+        )
+        .await
+    }
+
     pub async fn wait_for<R>(&self, resource: &R, checks: &[Check<R>]) -> Result<R, ()>
     where
         R: Resource<Scope = NamespaceResourceScope> + Clone + std::fmt::Debug + DeserializeOwned,
@@ -147,7 +294,12 @@ impl Context {
 
         'retry: for _ in 0..100 {
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-            let resource = api.get(&name).await.unwrap();
+
+            // Objects created by the controllers may not exist yet.
+            let Some(resource) = api.get_opt(&name).await.unwrap() else {
+                debug!("{name} does not exist yet");
+                continue 'retry;
+            };
 
             for check in checks.iter() {
                 if let Err(err) = check.perform(&resource) {
@@ -181,6 +333,31 @@ impl Context {
         Err(())
     }
 
+    /// Wait for the given resource to no longer exist.
+    #[allow(dead_code)]
+    pub async fn wait_for_deletion<R>(&self, resource: &R) -> Result<(), ()>
+    where
+        R: Resource<Scope = NamespaceResourceScope> + Clone + std::fmt::Debug + DeserializeOwned,
+        <R as Resource>::DynamicType: Default,
+    {
+        let client = self.inner.read().await.client.clone();
+
+        let api = Api::<R>::namespaced(client, resource.meta().namespace.as_ref().unwrap());
+        let name = resource.name_any();
+
+        for _ in 0..100 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+            if api.get_opt(&name).await.unwrap().is_none() {
+                info!("ok {name}: deleted");
+                return Ok(());
+            }
+        }
+
+        error!("timeout {name}: deleted");
+        Err(())
+    }
+
     pub async fn delete<R>(&self, resource: &R) -> Result<(), kube::Error>
     where
         R: Resource<Scope = NamespaceResourceScope> + Clone + DeserializeOwned + std::fmt::Debug,
@@ -221,6 +398,19 @@ impl Context {
             api.delete(&zone.name_any(), &DeleteParams::foreground())
                 .await
                 .ok();
+
+            // Zones carry a finalizer, so wait for the controller to remove
+            // it before it is stopped, or the zone would never go away.
+            tokio::time::timeout(
+                Duration::from_secs(30),
+                await_condition(
+                    api,
+                    &zone.name_any(),
+                    conditions::is_deleted(&zone.uid().unwrap_or_default()),
+                ),
+            )
+            .await
+            .ok();
         }
 
         for namespace in inner.namespaces.drain(..) {
@@ -232,9 +422,11 @@ impl Context {
     }
 }
 
+type CheckFn<R> = Box<dyn Fn(&R) -> Result<(), String> + Send + Sync>;
+
 pub struct Check<R> {
     pub name: String,
-    pub func: Box<dyn Fn(&R) -> Result<(), String> + Send + Sync>,
+    pub func: CheckFn<R>,
 }
 
 impl<R> Check<R> {
@@ -287,6 +479,28 @@ pub fn has_entry(fqdn: &str) -> Check<Zone> {
     })
 }
 
+/// Reference to the Record generated for the ingress host and address,
+/// for waiting on it to be created.
+#[allow(dead_code)]
+pub fn generated_record(namespace: &str, ingress: &str, host: &str, address: &str) -> Record {
+    Record {
+        metadata: ObjectMeta {
+            name: Some(record_name(ingress, host, &address.parse().unwrap())),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec: RecordSpec {
+            domain_name: DomainName::try_from(host).unwrap(),
+            zone_ref: None,
+            type_: Type::A,
+            class: kubizone_common::Class::IN,
+            ttl: None,
+            rdata: address.to_string(),
+        },
+        status: None,
+    }
+}
+
 #[allow(dead_code)]
 pub fn has_parent<R: DomainExt>(parent: &Zone) -> Check<R> {
     let parent = parent.zone_ref();
@@ -313,6 +527,76 @@ pub fn has_fqdn<R: DomainExt>() -> Check<R> {
     })
 }
 
+#[allow(dead_code)]
+pub fn has_fqdn_of<R: DomainExt>(fqdn: &str) -> Check<R> {
+    let fqdn = FullyQualifiedDomainName::try_from(fqdn).unwrap();
+    Check::new("has-fqdn-of", move |resource: &R| match resource.fqdn() {
+        Some(actual) if *actual == fqdn => Ok(()),
+        Some(actual) => Err(format!(r#"wrong fqdn got "{actual}", expected "{fqdn}""#)),
+        None => Err("no fqdn".to_string()),
+    })
+}
+
+#[allow(dead_code)]
+pub fn is_unpublished() -> Check<Record> {
+    Check::new(
+        "is-unpublished",
+        move |record: &Record| match Publication::of(record) {
+            Some(publication) => Err(format!(r#"published by "{}""#, publication.zone)),
+            None => Ok(()),
+        },
+    )
+}
+
+#[allow(dead_code)]
+pub fn is_published_by(zone: &Zone) -> Check<Record> {
+    let zone = zone.zone_ref().as_label();
+    Check::new("is-published-by", move |record: &Record| {
+        let Some(publication) = Publication::of(record) else {
+            return Err("not published".to_string());
+        };
+
+        if publication.zone != zone {
+            return Err(format!(
+                r#"published by "{}", expected "{zone}""#,
+                publication.zone
+            ));
+        }
+
+        if publication.observed_generation != record.metadata.generation {
+            return Err(format!(
+                "published generation {:?}, current generation {:?}",
+                publication.observed_generation, record.metadata.generation
+            ));
+        }
+
+        Ok(())
+    })
+}
+
+#[allow(dead_code)]
+pub fn reports_published(host: &str, record: &Record, zone: &Zone) -> Check<Ingress> {
+    let host = host.to_string();
+    let expected = json!({
+        "records": [record.name_any()],
+        "zones": [zone.zone_ref().to_string()],
+        "published": true,
+    });
+
+    Check::new("reports-published", move |ingress: &Ingress| {
+        let Some(report) = ingress.annotations().get(RECORDS_ANNOTATION) else {
+            return Err("no records annotation".to_string());
+        };
+
+        let report: serde_json::Value = serde_json::from_str(report).unwrap();
+        match report.get(&host) {
+            Some(actual) if *actual == expected => Ok(()),
+            Some(actual) => Err(format!("reported {actual}, expected {expected}")),
+            None => Err(format!("host {host} not reported")),
+        }
+    })
+}
+
 #[allow(dead_code)]
 pub fn not<R: 'static>(inner: Check<R>) -> Check<R> {
     Check {
@@ -325,8 +609,40 @@ pub fn not<R: 'static>(inner: Check<R>) -> Check<R> {
 }
 
 /// Creates a namespace for the tests to run in, and starts the controllers.
+#[allow(dead_code)]
 pub async fn run<F: Future<Output = ()> + Send + 'static>(
     func: impl Fn(Context) -> F + Send + 'static,
+) {
+    run_scoped(Scope::default(), func).await
+}
+
+/// Like [`run`], but with the controllers only watching the given scope.
+#[allow(dead_code)]
+pub async fn run_scoped<F: Future<Output = ()> + Send + 'static>(
+    scope: Scope,
+    func: impl Fn(Context) -> F + Send + 'static,
+) {
+    run_controllers(scope, None, func).await
+}
+
+/// Like [`run`], but additionally creating Records for the Ingresses in the given namespaces.
+#[allow(dead_code)]
+pub async fn run_with_ingresses<F: Future<Output = ()> + Send + 'static>(
+    namespaces: &[&str],
+    func: impl Fn(Context) -> F + Send + 'static,
+) {
+    let filter = IngressFilter {
+        namespaces: namespaces.iter().map(ToString::to_string).collect(),
+        ..Default::default()
+    };
+
+    run_controllers(Scope::default(), Some(filter), func).await
+}
+
+async fn run_controllers<F: Future<Output = ()> + Send + 'static>(
+    scope: Scope,
+    ingress_filter: Option<IngressFilter>,
+    func: impl Fn(Context) -> F + Send + 'static,
 ) {
     tracing_subscriber::fmt::init();
     let client = Client::try_default().await.unwrap();
@@ -342,11 +658,39 @@ pub async fn run<F: Future<Output = ()> + Send + 'static>(
         })),
     };
 
+    let policy = BackoffPolicy {
+        rejected_delay: Duration::from_secs(1),
+        ..Default::default()
+    };
+
     let controller_client = client.clone();
     tokio::spawn(async move {
+        let ingresses = async {
+            match ingress_filter {
+                Some(filter) => {
+                    kubizone::ingress::controller(
+                        IngressControllerContext {
+                            client: controller_client.clone(),
+                            // Long enough for tests to rely on watches, rather than requeues.
+                            requeue_time: Duration::from_secs(300),
+                            backoff: ErrorBackoff::new(policy),
+                            filter,
+                            conflicts: Default::default(),
+                            scope: scope.clone(),
+                        },
+                        Default::default(),
+                        Default::default(),
+                    )
+                    .await
+                }
+                None => futures::future::pending().await,
+            }
+        };
+
         tokio::select! {
-            _ = kubizone::zone::controller(ZoneControllerContext { client: controller_client.clone(), requeue_time: Duration::from_secs(1) }) => (),
-            _ = kubizone::record::controller(RecordControllerContext { client: controller_client.clone(), requeue_time: Duration::from_secs(1) }) => ()
+            _ = kubizone::zone::controller(ZoneControllerContext { client: controller_client.clone(), requeue_time: Duration::from_secs(1), backoff: ErrorBackoff::new(policy), scope: scope.clone(), ttl_policy: Default::default(), soa: Default::default() }, Default::default(), Default::default()) => (),
+            _ = kubizone::record::controller(RecordControllerContext { client: controller_client.clone(), requeue_time: Duration::from_secs(1), backoff: ErrorBackoff::new(policy), scope: scope.clone() }, Default::default(), Default::default()) => (),
+            _ = ingresses => (),
         }
     });
 
@@ -0,0 +1,114 @@
+use std::num::NonZeroU64;
+
+use indoc::indoc;
+use kubizone::{
+    config::{Config, Format},
+    resolution::TtlPolicy,
+};
+
+#[test]
+fn yaml() {
+    let config = Config::parse(
+        indoc! {r#"
+            requeueTimeSecs: 10
+            controllers:
+              record: false
+              ingress: true
+            scope:
+              namespaces: [dns]
+              shard: a
+            ingress:
+              classes: [nginx]
+            soa:
+              nameserver: ns1.example.org.
+        "#},
+        Format::Yaml,
+    )
+    .unwrap();
+
+    assert_eq!(config.requeue_time_secs, NonZeroU64::new(10));
+    assert!(!config.dry_run);
+    assert!(config.controllers.zone);
+    assert!(!config.controllers.record);
+    assert_eq!(config.controllers.ingress, Some(true));
+    assert_eq!(config.scope.namespaces, vec!["dns".to_string()]);
+    assert_eq!(config.scope.shard.as_deref(), Some("a"));
+    assert_eq!(config.ingress.classes, vec!["nginx".to_string()]);
+    assert_eq!(config.ingress.selector, None);
+    assert_eq!(
+        config
+            .soa
+            .nameserver
+            .map(|fqdn| fqdn.to_string())
+            .as_deref(),
+        Some("ns1.example.org.")
+    );
+    assert_eq!(config.soa.mailbox, None);
+}
+
+#[test]
+fn toml() {
+    let config = Config::parse(
+        indoc! {r#"
+            requeueTimeSecs = 10
+            dryRun = true
+            rrsetTtl = "first-created"
+
+            [scope]
+            zoneSelector = "shard=a"
+
+            [ingress]
+            selector = "dns=public"
+
+            [soa]
+            mailbox = "hostmaster.example.org."
+        "#},
+        Format::Toml,
+    )
+    .unwrap();
+
+    assert_eq!(config.requeue_time_secs, NonZeroU64::new(10));
+    assert!(config.dry_run);
+    assert_eq!(config.rrset_ttl, Some(TtlPolicy::FirstCreated));
+    assert_eq!(config.scope.zone_selector.as_deref(), Some("shard=a"));
+    assert_eq!(config.ingress.selector.as_deref(), Some("dns=public"));
+    assert_eq!(
+        config.soa.mailbox.map(|fqdn| fqdn.to_string()).as_deref(),
+        Some("hostmaster.example.org.")
+    );
+}
+
+#[test]
+fn empty() {
+    assert_eq!(Config::parse("", Format::Yaml).unwrap(), Config::default());
+    assert_eq!(Config::parse("", Format::Toml).unwrap(), Config::default());
+}
+
+#[test]
+fn errors_report_location() {
+    let err = Config::parse(
+        indoc! {r#"
+            scope:
+              namespaces: [dns]
+              shards: a
+        "#},
+        Format::Yaml,
+    )
+    .unwrap_err();
+
+    assert_eq!(err.location.map(|(line, _)| line), Some(3), "{err}");
+    assert!(err.message.contains("shards"), "{err}");
+
+    let err = Config::parse(
+        indoc! {r#"
+            # Requeue quickly.
+
+            requeueTimeSecs = 0
+        "#},
+        Format::Toml,
+    )
+    .unwrap_err();
+
+    assert_eq!(err.location.map(|(line, _)| line), Some(3), "{err}");
+    assert!(err.message.contains("nonzero"), "{err}");
+}
@@ -0,0 +1,68 @@
+use k8s_openapi::{
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+        CustomResourceConversion, CustomResourceDefinition,
+    },
+    serde_json::{self, json},
+};
+use kube::CustomResourceExt;
+use kubizone::crds::{compare, definitions, render};
+use kubizone_crds::v1alpha1::Zone;
+use serde::Deserialize;
+
+#[test]
+fn render_round_trips() {
+    let rendered = render().unwrap();
+
+    let parsed: Vec<CustomResourceDefinition> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(|document| CustomResourceDefinition::deserialize(document).unwrap())
+        .collect();
+
+    assert_eq!(parsed, definitions());
+}
+
+#[test]
+fn identical_definitions_match() {
+    for crd in definitions() {
+        assert!(compare(&crd, &crd).is_empty());
+    }
+}
+
+#[test]
+fn missing_version_differs() {
+    let expected = Zone::crd();
+
+    let mut installed = expected.clone();
+    installed.spec.versions.clear();
+
+    let differences = compare(&expected, &installed);
+    assert_eq!(differences.len(), 1, "{differences:?}");
+    assert!(differences[0].contains("is missing"), "{differences:?}");
+}
+
+#[test]
+fn schema_change_differs() {
+    let expected = Zone::crd();
+
+    let mut installed = expected.clone();
+    let schema = installed.spec.versions[0]
+        .schema
+        .as_mut()
+        .unwrap()
+        .open_api_v3_schema
+        .as_mut()
+        .unwrap();
+    schema.description = Some("outdated".to_string());
+    schema.x_kubernetes_preserve_unknown_fields = Some(true);
+
+    let differences = compare(&expected, &installed);
+    assert_eq!(differences.len(), 1, "{differences:?}");
+    assert!(differences[0].contains("schema"), "{differences:?}");
+
+    // Fields outside of the group, names, versions and schemas are ignored,
+    // since the API server fills in defaults for them.
+    let mut installed = expected.clone();
+    installed.spec.conversion = Some(
+        serde_json::from_value::<CustomResourceConversion>(json!({ "strategy": "None" })).unwrap(),
+    );
+    assert!(compare(&expected, &installed).is_empty());
+}
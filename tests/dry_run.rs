@@ -0,0 +1,64 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use std::time::Duration;
+
+    use kubizone::Scope;
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        let scope = Scope {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        crate::common::run_scoped(scope, async move |ctx: Context| {
+            ctx.namespace("kubizone-dry-run").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-dry-run",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        namespaces: vec![],
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            types: vec![],
+                        }],
+                        zones: vec![],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let good_example_org = ctx
+                .a_record("kubizone-dry-run", "good-example-org", "good.example.org.")
+                .await
+                .unwrap();
+
+            // Give the controllers time to reconcile both objects.
+            tokio::time::sleep(Duration::from_secs(5)).await;
+
+            ctx.wait_for(&example_org, &[not(has_fqdn()), not(has_serial())])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &good_example_org,
+                &[not(has_fqdn()), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
@@ -0,0 +1,215 @@
+use k8s_openapi::serde_json::{self, json, Value};
+use kubizone::{
+    explain::{explain_record, explain_zone, Role},
+    resolution::Resolution,
+};
+use kubizone_common::FullyQualifiedDomainName;
+use kubizone_crds::v1alpha1::{Record, Zone, ZoneStatus};
+
+fn zone(namespace: &str, name: &str, spec: Value) -> Zone {
+    let mut zone: Zone = serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Zone",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "uid": format!("{namespace}/{name}"),
+        },
+        "spec": spec,
+    }))
+    .unwrap();
+
+    if let Ok(fqdn) = FullyQualifiedDomainName::try_from(zone.spec.domain_name.to_string()) {
+        zone.status = Some(ZoneStatus {
+            fqdn: Some(fqdn),
+            ..Default::default()
+        });
+    }
+    zone
+}
+
+fn record(namespace: &str, spec: Value) -> Record {
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Record",
+        "metadata": {
+            "name": "www",
+            "namespace": namespace,
+        },
+        "spec": spec,
+    }))
+    .unwrap()
+}
+
+fn zones() -> Vec<Zone> {
+    vec![
+        zone(
+            "default",
+            "org",
+            json!({
+                "domainName": "org.",
+                "delegations": [{ "records": [{ "pattern": "*" }], "zones": ["*"] }],
+            }),
+        ),
+        zone(
+            "default",
+            "example-org",
+            json!({
+                "domainName": "example.org.",
+                "delegations": [{
+                    "namespaces": ["team"],
+                    "records": [{ "pattern": "*" }],
+                }, {
+                    "records": [
+                        { "pattern": "mail" },
+                        { "pattern": "www", "types": ["AAAA"] },
+                    ],
+                    "zones": ["sub"],
+                }],
+            }),
+        ),
+    ]
+}
+
+#[test]
+fn records_list_every_candidate() {
+    let zones = zones();
+    let www = record(
+        "default",
+        json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+
+    let explanation = explain_record(&www, &zones);
+
+    let candidates: Vec<_> = explanation
+        .candidates
+        .iter()
+        .map(|candidate| (candidate.zone.as_str(), candidate.role.clone()))
+        .collect();
+    assert_eq!(
+        candidates,
+        [
+            ("default/example-org", Role::MostImmediate),
+            (
+                "default/org",
+                Role::Shadowed {
+                    by: "default/example-org".to_string()
+                }
+            ),
+        ]
+    );
+
+    // Even though org. would allow the record, only example.org. decides.
+    assert!(!explanation.candidates[0].allows());
+    assert!(explanation.candidates[1].allows());
+    assert!(matches!(
+        explanation.resolution,
+        Resolution::Unadopted { .. }
+    ));
+
+    let delegations = &explanation.candidates[0].delegations;
+    assert!(!delegations[0].namespace.allowed);
+    assert!(delegations[0].rules[0].allowed);
+
+    assert!(delegations[1].namespace.allowed);
+    assert!(!delegations[1].rules[0].allowed);
+    assert!(delegations[1].rules[0].reason.contains("does not match"));
+    assert!(!delegations[1].rules[1].allowed);
+    assert_eq!(delegations[1].rules[1].reason, "type A is not delegated");
+
+    let rendered = explanation.to_string();
+    assert!(rendered.contains("delegation #1: denied"), "{rendered}");
+    assert!(rendered.contains("result: not adopted"), "{rendered}");
+}
+
+#[test]
+fn records_follow_zone_refs() {
+    let zones = zones();
+    let www = record(
+        "team",
+        json!({
+            "domainName": "www",
+            "zoneRef": { "name": "example-org", "namespace": "default" },
+            "type": "A",
+            "rdata": "192.168.0.1",
+        }),
+    );
+
+    let explanation = explain_record(&www, &zones);
+
+    assert_eq!(explanation.candidates.len(), 1);
+    assert_eq!(explanation.candidates[0].role, Role::Referenced);
+    assert!(explanation.candidates[0].delegations[0].allows());
+    assert!(explanation.owner.as_ref().unwrap().allowed);
+    assert!(matches!(explanation.resolution, Resolution::Adopted { .. }));
+}
+
+#[test]
+fn wildcard_owners_are_reported() {
+    let zones = zones();
+    let ns = record(
+        "default",
+        json!({ "domainName": "*.org.", "type": "NS", "rdata": "ns.org." }),
+    );
+
+    let explanation = explain_record(&ns, &zones);
+
+    // The delegations of org. would allow the record, but its owner name does not.
+    assert!(!explanation.owner.as_ref().unwrap().allowed);
+    assert!(explanation.candidates[0].allows());
+    assert!(matches!(
+        explanation.resolution,
+        Resolution::Unadopted { .. }
+    ));
+}
+
+#[test]
+fn zones_evaluate_zone_delegations() {
+    let zones = zones();
+
+    let sub = zone(
+        "default",
+        "sub",
+        json!({ "domainName": "sub.example.org.", "delegations": [] }),
+    );
+    let explanation = explain_zone(&sub, &zones);
+    assert!(explanation.candidates[0].allows());
+    assert!(matches!(explanation.resolution, Resolution::Adopted { .. }));
+
+    let other = zone(
+        "default",
+        "other",
+        json!({ "domainName": "other.example.org.", "delegations": [] }),
+    );
+    let explanation = explain_zone(&other, &zones);
+    let delegations = &explanation.candidates[0].delegations;
+
+    // The first delegation has no zone rules at all.
+    assert!(delegations[0].rules.is_empty());
+    assert!(!delegations[1].rules[0].allowed);
+    assert!(!explanation.candidates[0].allows());
+
+    // Zones are never candidates for their own adoption.
+    let explanation = explain_zone(&zones[1], &zones);
+    assert_eq!(explanation.candidates.len(), 1);
+    assert_eq!(explanation.candidates[0].zone, "default/org");
+}
+
+#[test]
+fn zones_being_deleted_are_not_candidates() {
+    let mut zones = zones();
+    zones[1].metadata.deletion_timestamp =
+        serde_json::from_value(json!("2024-05-17T12:00:00Z")).unwrap();
+
+    let www = record(
+        "default",
+        json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+
+    // With example.org. about to detach its children, org. adopts the record.
+    let explanation = explain_record(&www, &zones);
+    assert_eq!(explanation.candidates.len(), 1);
+    assert_eq!(explanation.candidates[0].zone, "default/org");
+    assert_eq!(explanation.candidates[0].role, Role::MostImmediate);
+    assert!(matches!(explanation.resolution, Resolution::Adopted { .. }));
+}
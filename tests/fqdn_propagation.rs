@@ -0,0 +1,108 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use k8s_openapi::serde_json::json;
+    use kube::{
+        api::{Patch, PatchParams},
+        Api, ResourceExt,
+    };
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation, Zone};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-fqdn-propagation").await.unwrap();
+
+            let delegations = [Delegation {
+                records: vec![RecordDelegation {
+                    pattern: Pattern::try_from("*").unwrap(),
+                    types: vec![],
+                }],
+                namespaces: vec![],
+                zones: vec![Pattern::try_from("*").unwrap()],
+            }];
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-fqdn-propagation",
+                    "example",
+                    "example.org.",
+                    &delegations,
+                )
+                .await
+                .unwrap();
+
+            let sub_example_org = ctx
+                .zone_with_ref(
+                    "kubizone-fqdn-propagation",
+                    "sub",
+                    "sub",
+                    &example_org,
+                    &delegations,
+                )
+                .await
+                .unwrap();
+
+            let www_sub_example_org = ctx
+                .a_record_with_ref("kubizone-fqdn-propagation", "www", "www", &sub_example_org)
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &www_sub_example_org,
+                &[
+                    has_fqdn_of("www.sub.example.org."),
+                    has_parent(&sub_example_org),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(&sub_example_org, &[has_entry("www.sub.example.org.")])
+                .await
+                .unwrap();
+
+            // Move the top level zone to a different domain.
+            Api::<Zone>::namespaced(ctx.client().await, "kubizone-fqdn-propagation")
+                .patch(
+                    &example_org.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({ "spec": { "domainName": "example.com." } })),
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&sub_example_org, &[has_fqdn_of("sub.example.com.")])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &www_sub_example_org,
+                &[
+                    has_fqdn_of("www.sub.example.com."),
+                    has_parent(&sub_example_org),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &sub_example_org,
+                &[
+                    has_entry("www.sub.example.com."),
+                    not(has_entry("www.sub.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
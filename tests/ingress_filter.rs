@@ -0,0 +1,35 @@
+use kubizone::ingress::IngressFilter;
+
+#[test]
+fn empty_filter_covers_everything() {
+    let filter = IngressFilter::default();
+
+    assert!(filter.covers_namespace("default"));
+    assert!(filter.covers_class(Some("nginx")));
+    assert!(filter.covers_class(None));
+}
+
+#[test]
+fn namespaces_are_matched_exactly() {
+    let filter = IngressFilter {
+        namespaces: vec!["default".to_string(), "web".to_string()],
+        ..Default::default()
+    };
+
+    assert!(filter.covers_namespace("default"));
+    assert!(filter.covers_namespace("web"));
+    assert!(!filter.covers_namespace("webapps"));
+    assert!(!filter.covers_namespace(""));
+}
+
+#[test]
+fn classless_ingresses_are_only_covered_without_class_filter() {
+    let filter = IngressFilter {
+        classes: vec!["nginx".to_string()],
+        ..Default::default()
+    };
+
+    assert!(filter.covers_class(Some("nginx")));
+    assert!(!filter.covers_class(Some("traefik")));
+    assert!(!filter.covers_class(None));
+}
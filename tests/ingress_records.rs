@@ -0,0 +1,94 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use k8s_openapi::{api::networking::v1::Ingress, serde_json::json};
+    use kube::{
+        api::{Patch, PatchParams},
+        Api,
+    };
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run_with_ingresses(
+            &["kubizone-ingress-records"],
+            async move |ctx: Context| {
+                ctx.namespace("kubizone-ingress-records").await.unwrap();
+
+                let web = ctx
+                    .ingress(
+                        "kubizone-ingress-records",
+                        "web",
+                        &["www.example.org"],
+                        "127.0.0.1",
+                    )
+                    .await
+                    .unwrap();
+
+                let www_example_org = generated_record(
+                    "kubizone-ingress-records",
+                    "web",
+                    "www.example.org",
+                    "127.0.0.1",
+                );
+
+                ctx.wait_for(&www_example_org, &[has_fqdn_of("www.example.org.")])
+                    .await
+                    .unwrap();
+
+                // The zone is created only after the ingress has been reconciled, so
+                // the report can only be refreshed by watching the generated record.
+                let example_org = ctx
+                    .zone(
+                        "kubizone-ingress-records",
+                        "example-org",
+                        "example.org.",
+                        &[Delegation {
+                            records: vec![RecordDelegation {
+                                pattern: Pattern::try_from("www").unwrap(),
+                                types: vec![],
+                            }],
+                            namespaces: vec![],
+                            zones: vec![],
+                        }],
+                    )
+                    .await
+                    .unwrap();
+
+                ctx.wait_for(
+                    &web,
+                    &[reports_published(
+                        "www.example.org",
+                        &www_example_org,
+                        &example_org,
+                    )],
+                )
+                .await
+                .unwrap();
+
+                // Once the ingress loses its load balancer address,
+                // the record generated for it is deleted.
+                Api::<Ingress>::namespaced(ctx.client().await, "kubizone-ingress-records")
+                    .patch_status(
+                        "web",
+                        &PatchParams::default(),
+                        &Patch::Merge(json!({
+                            "status": { "loadBalancer": { "ingress": [] } }
+                        })),
+                    )
+                    .await
+                    .unwrap();
+
+                ctx.wait_for_deletion(&www_example_org).await.unwrap();
+            },
+        )
+        .await;
+    }
+}
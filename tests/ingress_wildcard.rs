@@ -0,0 +1,67 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run_with_ingresses(
+            &["kubizone-ingress-wildcard"],
+            async move |ctx: Context| {
+                ctx.namespace("kubizone-ingress-wildcard").await.unwrap();
+
+                let example_org = ctx
+                    .zone(
+                        "kubizone-ingress-wildcard",
+                        "example-org",
+                        "example.org.",
+                        &[Delegation {
+                            records: vec![RecordDelegation {
+                                pattern: Pattern::try_from("*.apps").unwrap(),
+                                types: vec![],
+                            }],
+                            namespaces: vec![],
+                            zones: vec![],
+                        }],
+                    )
+                    .await
+                    .unwrap();
+
+                ctx.ingress(
+                    "kubizone-ingress-wildcard",
+                    "web",
+                    &["*.apps.example.org"],
+                    "127.0.0.1",
+                )
+                .await
+                .unwrap();
+
+                let wildcard_apps_example_org = generated_record(
+                    "kubizone-ingress-wildcard",
+                    "web",
+                    "*.apps.example.org",
+                    "127.0.0.1",
+                );
+
+                ctx.wait_for(
+                    &wildcard_apps_example_org,
+                    &[has_fqdn_of("*.apps.example.org."), has_parent(&example_org)],
+                )
+                .await
+                .unwrap();
+
+                ctx.wait_for(&example_org, &[has_entry("*.apps.example.org.")])
+                    .await
+                    .unwrap();
+            },
+        )
+        .await;
+    }
+}
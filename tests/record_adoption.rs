@@ -1,5 +1,3 @@
-#![feature(async_closure)]
-
 #[cfg(feature = "dev")]
 mod common;
 
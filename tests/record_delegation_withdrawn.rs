@@ -1,5 +1,3 @@
-#![feature(async_closure)]
-
 #[cfg(feature = "dev")]
 mod common;
 
@@ -70,7 +68,7 @@ mod tests {
                 &PatchParams::apply("record-delegation-withdrawn"),
                 &Patch::<Zone>::Json(json_patch::Patch(vec![PatchOperation::Remove(
                     RemoveOperation {
-                        path: jsonptr::Pointer::new(&["spec", "delegations", "0"]),
+                        path: jsonptr::Pointer::new(["spec", "delegations", "0"]),
                     },
                 )])),
             )
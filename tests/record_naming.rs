@@ -0,0 +1,100 @@
+use std::{net::IpAddr, str::FromStr};
+
+use kubizone::ingress::{label_value, record_name};
+
+fn is_valid_name(name: &str) -> bool {
+    name.len() <= 253
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+fn is_valid_label(value: &str) -> bool {
+    value.len() <= 63
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && value.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+#[test]
+fn deterministic() {
+    let addr = IpAddr::from_str("192.168.0.1").unwrap();
+
+    assert_eq!(
+        record_name("web", "www.example.org", &addr),
+        record_name("web", "www.example.org", &addr)
+    );
+}
+
+#[test]
+fn distinct_inputs_are_unlikely_to_collide() {
+    let addr = IpAddr::from_str("192.168.0.1").unwrap();
+
+    assert_ne!(
+        record_name("web", "a-b.c", &addr),
+        record_name("web", "a.b-c", &addr)
+    );
+
+    assert_ne!(
+        record_name("web-a", "b.example.org", &addr),
+        record_name("web", "a-b.example.org", &addr)
+    );
+}
+
+#[test]
+fn long_inputs_are_truncated() {
+    let addr = IpAddr::from_str("2001:db8:85a3:8d3:1319:8a2e:370:7348").unwrap();
+    let host = format!("{}.example.org", ["abcdefghij"; 22].join("."));
+
+    let name = record_name(&"ingress".repeat(30), &host, &addr);
+    assert!(is_valid_name(&name), "{name}");
+
+    // Hosts which only differ past the truncation point must still differ.
+    let other = record_name(
+        &"ingress".repeat(30),
+        &host.replace("example", "elpmaxe"),
+        &addr,
+    );
+    assert!(is_valid_name(&other), "{other}");
+    assert_ne!(name, other);
+}
+
+#[test]
+fn ipv6_names_are_valid() {
+    let addr = IpAddr::from_str("::1").unwrap();
+
+    let name = record_name("web", "www.example.org", &addr);
+    assert!(is_valid_name(&name), "{name}");
+}
+
+#[test]
+fn label_values() {
+    // Valid values are used as-is.
+    assert_eq!(label_value("www.example.org"), "www.example.org");
+    assert_eq!(label_value("192.168.0.1"), "192.168.0.1");
+
+    for value in [
+        "2001:db8::1",
+        "::1",
+        &format!("{}.example.org", ["abcdefghij"; 10].join(".")),
+    ] {
+        let label = label_value(value);
+        assert!(is_valid_label(&label), "{value} => {label}");
+    }
+}
+
+#[test]
+fn wildcard_names_are_valid() {
+    let addr = IpAddr::from_str("192.168.0.1").unwrap();
+
+    let name = record_name("web", "*.apps.example.org", &addr);
+    assert!(is_valid_name(&name), "{name}");
+    assert!(name.starts_with("web-wildcard-apps-example-org-"), "{name}");
+
+    let label = label_value("*.apps.example.org");
+    assert!(is_valid_label(&label), "{label}");
+}
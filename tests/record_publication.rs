@@ -0,0 +1,100 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use k8s_openapi::serde_json::json;
+    use kube::{
+        api::{Patch, PatchParams},
+        Api, ResourceExt,
+    };
+    use kubizone::resolution::Publication;
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, Record, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-record-publication").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-record-publication",
+                    "example",
+                    "example.org.",
+                    &[Delegation {
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            types: vec![],
+                        }],
+                        namespaces: vec![],
+                        zones: vec![],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .a_record("kubizone-record-publication", "www", "www.example.org.")
+                .await
+                .unwrap();
+
+            ctx.wait_for(&www_example_org, &[is_published_by(&example_org)])
+                .await
+                .unwrap();
+
+            // Change the record's content, and wait for the new generation to be published.
+            Api::<Record>::namespaced(ctx.client().await, "kubizone-record-publication")
+                .patch(
+                    &www_example_org.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({ "spec": { "rdata": "127.0.0.2" } })),
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .wait_for(&www_example_org, &[is_published_by(&example_org)])
+                .await
+                .unwrap();
+
+            let example_org = ctx
+                .wait_for(&example_org, &[has_entry("www.example.org.")])
+                .await
+                .unwrap();
+
+            let publication = Publication::of(&www_example_org).unwrap();
+            assert_eq!(publication.observed_generation, Some(2));
+            assert_eq!(Some(publication.serial), example_org.serial());
+            assert!(example_org
+                .status
+                .as_ref()
+                .unwrap()
+                .entries
+                .iter()
+                .any(|entry| entry.rdata == "127.0.0.2"));
+
+            // Move the record out of the zone, and wait for the publication to be withdrawn.
+            Api::<Record>::namespaced(ctx.client().await, "kubizone-record-publication")
+                .patch(
+                    &www_example_org.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({ "spec": { "domainName": "www.example.net." } })),
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &www_example_org,
+                &[not(has_parent(&example_org)), is_unpublished()],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
@@ -0,0 +1,159 @@
+use k8s_openapi::serde_json::{self, json, Value};
+use kube::ResourceExt as _;
+use kubizone::registry::{find_conflict, Owner, OWNER_ANNOTATION};
+use kubizone_common::{FullyQualifiedDomainName, Type};
+use kubizone_crds::v1alpha1::Record;
+
+fn fqdn(name: &str) -> FullyQualifiedDomainName {
+    FullyQualifiedDomainName::try_from(name).unwrap()
+}
+
+fn owner(name: &str) -> Owner {
+    Owner {
+        kind: "Ingress".to_string(),
+        namespace: "default".to_string(),
+        name: name.to_string(),
+        uid: Some(format!("{name}-uid")),
+    }
+}
+
+fn record(name: &str, created: &str, metadata: Value) -> Record {
+    let mut record: Record = serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Record",
+        "metadata": metadata,
+        "spec": { "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" },
+    }))
+    .unwrap();
+
+    record.metadata.name = Some(name.to_string());
+    record.metadata.namespace = Some("default".to_string());
+    record.metadata.creation_timestamp = serde_json::from_value(json!(created)).unwrap();
+    record
+}
+
+/// Record generated by `owner`, identified through the owner annotation.
+fn generated(name: &str, created: &str, owner: &Owner) -> Record {
+    record(
+        name,
+        created,
+        json!({ "annotations": { OWNER_ANNOTATION: owner.as_annotation() } }),
+    )
+}
+
+fn conflict(owner: &Owner, records: &[Record]) -> Option<String> {
+    find_conflict(owner, &fqdn("www.example.org."), Type::A, records)
+        .map(|record| record.name_any())
+}
+
+#[test]
+fn owners_recognize_their_records() {
+    let web = owner("web");
+    let api = owner("api");
+
+    let annotated = generated("annotated", "2024-05-01T12:00:00Z", &web);
+    assert!(web.owns(&annotated));
+    assert!(!api.owns(&annotated));
+
+    // Records generated before the owner annotation was introduced are
+    // recognized through their owner references.
+    let legacy = record(
+        "legacy",
+        "2024-05-01T12:00:00Z",
+        json!({ "ownerReferences": [{
+            "apiVersion": "networking.k8s.io/v1",
+            "kind": "Ingress",
+            "name": "web",
+            "uid": "web-uid",
+        }] }),
+    );
+    assert!(web.owns(&legacy));
+    assert!(!api.owns(&legacy));
+
+    let hand_written = record("hand-written", "2024-05-01T12:00:00Z", json!({}));
+    assert!(!web.owns(&hand_written));
+}
+
+#[test]
+fn own_records_do_not_conflict() {
+    let web = owner("web");
+
+    assert_eq!(
+        conflict(&web, &[generated("web", "2024-05-01T12:00:00Z", &web)]),
+        None
+    );
+}
+
+#[test]
+fn hand_written_records_take_precedence() {
+    let web = owner("web");
+
+    let records = [
+        generated("web", "2024-05-01T12:00:00Z", &web),
+        record("hand-written", "2024-05-02T12:00:00Z", json!({})),
+    ];
+
+    assert_eq!(conflict(&web, &records).as_deref(), Some("hand-written"));
+}
+
+#[test]
+fn earlier_owner_takes_precedence() {
+    let web = owner("web");
+    let api = owner("api");
+
+    let records = [
+        generated("web", "2024-05-01T12:00:00Z", &web),
+        generated("api", "2024-05-02T12:00:00Z", &api),
+    ];
+
+    assert_eq!(conflict(&web, &records), None);
+    assert_eq!(conflict(&api, &records).as_deref(), Some("web"));
+
+    // Owners without a record of their own yet never hold the claim.
+    let late = owner("late");
+    assert!(conflict(&late, &records).is_some());
+}
+
+#[test]
+fn ties_are_broken_by_name() {
+    let web = owner("web");
+    let api = owner("api");
+
+    let records = [
+        generated("web", "2024-05-01T12:00:00Z", &web),
+        generated("api", "2024-05-01T12:00:00Z", &api),
+    ];
+
+    assert_eq!(conflict(&api, &records), None);
+    assert_eq!(conflict(&web, &records).as_deref(), Some("api"));
+}
+
+#[test]
+fn records_owned_by_other_operators_are_hand_written() {
+    let web = owner("web");
+
+    let owned_by = |name: &str, api_version: &str, kind: &str| {
+        record(
+            name,
+            "2024-05-02T12:00:00Z",
+            json!({ "ownerReferences": [{
+                "apiVersion": api_version,
+                "kind": kind,
+                "name": name,
+                "uid": format!("{name}-uid"),
+            }] }),
+        )
+    };
+
+    let records = [
+        generated("web", "2024-05-01T12:00:00Z", &web),
+        owned_by("api", "networking.k8s.io/v1", "Ingress"),
+    ];
+    assert_eq!(conflict(&web, &records), None);
+
+    let records = [
+        generated("web", "2024-05-01T12:00:00Z", &web),
+        owned_by("app", "argoproj.io/v1alpha1", "Application"),
+    ];
+    assert_eq!(conflict(&web, &records).as_deref(), Some("app"));
+}
@@ -0,0 +1,1042 @@
+use k8s_openapi::serde_json::{self, json, Value};
+use kube::{
+    runtime::{
+        reflector::{store::Writer, ObjectRef},
+        watcher,
+    },
+    Resource, ResourceExt as _,
+};
+use kubizone::{
+    children_in,
+    resolution::{
+        allowed_owner, is_child, next_serial, normalized_ttls, publication, published_records,
+        resolve_record, resolve_zone, zone_entries, zone_hash, zone_ref_cycle, zone_status,
+        Publication, Resolution, Snapshot, SoaDefaults, TtlPolicy, FLATTEN_ANNOTATION,
+        PUBLISHED_ANNOTATION,
+    },
+};
+use kubizone_common::{DomainName, FullyQualifiedDomainName, Type};
+use kubizone_crds::{
+    v1alpha1::{DomainExt as _, Record, Zone, ZoneRef, ZoneStatus},
+    PARENT_ZONE_LABEL,
+};
+use time::{Month, OffsetDateTime};
+
+fn fqdn(name: &str) -> FullyQualifiedDomainName {
+    FullyQualifiedDomainName::try_from(name).unwrap()
+}
+
+fn noon(year: i32, day: u8) -> OffsetDateTime {
+    time::Date::from_calendar_date(year, Month::May, day)
+        .unwrap()
+        .with_hms(12, 0, 0)
+        .unwrap()
+        .assume_utc()
+}
+
+fn zone_ref(name: &str, namespace: &str) -> ZoneRef {
+    ZoneRef {
+        name: name.to_string(),
+        namespace: Some(namespace.to_string()),
+    }
+}
+
+fn zone(namespace: &str, name: &str, mut spec: Value) -> Zone {
+    spec.as_object_mut()
+        .unwrap()
+        .entry("delegations")
+        .or_insert(json!([]));
+
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Zone",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "uid": format!("{namespace}/{name}"),
+        },
+        "spec": spec,
+    }))
+    .unwrap()
+}
+
+/// Zone which has already been assigned its fqdn.
+fn resolved_zone(namespace: &str, name: &str, spec: Value) -> Zone {
+    let mut zone = zone(namespace, name, spec);
+    let domain_name = zone.spec.domain_name.to_string();
+
+    zone.status = Some(ZoneStatus {
+        fqdn: Some(fqdn(&domain_name)),
+        ..Default::default()
+    });
+    zone
+}
+
+fn record(namespace: &str, name: &str, spec: Value) -> Record {
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Record",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+        },
+        "spec": spec,
+    }))
+    .unwrap()
+}
+
+/// Record which has been assigned its fqdn, and labelled with its parent.
+fn adopted_record(namespace: &str, name: &str, parent: &Zone, spec: Value) -> Record {
+    let mut record = record(namespace, name, spec);
+    let domain_name = record.spec.domain_name.to_string();
+
+    record.status = serde_json::from_value(json!({ "fqdn": domain_name })).unwrap();
+    record.metadata.labels =
+        Some([(PARENT_ZONE_LABEL.to_string(), parent.zone_ref().as_label())].into());
+    record
+}
+
+fn example_org() -> Zone {
+    resolved_zone(
+        "default",
+        "example-org",
+        json!({
+            "domainName": "example.org.",
+            "delegations": [{
+                "namespaces": ["default"],
+                "records": [{ "pattern": "*" }, { "pattern": "*.apps" }],
+                "zones": ["sub"],
+            }, {
+                "namespaces": ["team"],
+                "records": [{ "pattern": "team", "types": ["A"] }],
+                "zones": ["team"],
+            }],
+        }),
+    )
+}
+
+fn sub_example_org() -> Zone {
+    resolved_zone(
+        "default",
+        "sub-example-org",
+        json!({
+            "domainName": "sub.example.org.",
+            "delegations": [{ "records": [{ "pattern": "*" }], "zones": ["deep"] }],
+        }),
+    )
+}
+
+#[test]
+fn wildcard_owners() {
+    assert!(allowed_owner(&fqdn("*.example.org."), Type::A));
+    assert!(allowed_owner(&fqdn("www.example.org."), Type::NS));
+    assert!(!allowed_owner(&fqdn("*.example.org."), Type::NS));
+    assert!(!allowed_owner(&fqdn("*.example.org."), Type::SOA));
+    assert!(!allowed_owner(&fqdn("*.example.org."), Type::DNAME));
+}
+
+#[test]
+fn zone_without_parent_is_top_level() {
+    let zone = zone(
+        "default",
+        "example-org",
+        json!({ "domainName": "example.org." }),
+    );
+
+    assert_eq!(
+        resolve_zone(&zone, &[]),
+        Resolution::TopLevel {
+            fqdn: fqdn("example.org.")
+        }
+    );
+}
+
+#[test]
+fn zone_is_adopted_by_longest_parent() {
+    let zones = [example_org(), sub_example_org()];
+
+    let child = zone(
+        "default",
+        "deep",
+        json!({ "domainName": "deep.sub.example.org." }),
+    );
+    assert_eq!(
+        resolve_zone(&child, &zones),
+        Resolution::Adopted {
+            fqdn: fqdn("deep.sub.example.org."),
+            parent: zone_ref("sub-example-org", "default"),
+        }
+    );
+
+    // sub.example.org. itself is adopted by example.org., rather than itself.
+    assert_eq!(
+        resolve_zone(&zones[1], &zones),
+        Resolution::Adopted {
+            fqdn: fqdn("sub.example.org."),
+            parent: zone_ref("example-org", "default"),
+        }
+    );
+}
+
+#[test]
+fn zone_without_delegation_is_unadopted() {
+    let zones = [example_org()];
+
+    let other = zone(
+        "default",
+        "other",
+        json!({ "domainName": "other.example.org." }),
+    );
+    assert!(matches!(
+        resolve_zone(&other, &zones),
+        Resolution::Unadopted { fqdn: Some(fqdn), .. } if fqdn.to_string() == "other.example.org."
+    ));
+
+    // Delegation of "team" only covers the team namespace.
+    let team = zone(
+        "default",
+        "team",
+        json!({ "domainName": "team.example.org." }),
+    );
+    assert!(matches!(
+        resolve_zone(&team, &zones),
+        Resolution::Unadopted { .. }
+    ));
+}
+
+#[test]
+fn zone_refs_are_followed() {
+    let zones = [example_org()];
+
+    let team = zone(
+        "team",
+        "team",
+        json!({
+            "domainName": "team",
+            "zoneRef": { "name": "example-org", "namespace": "default" },
+        }),
+    );
+    assert_eq!(
+        resolve_zone(&team, &zones),
+        Resolution::Adopted {
+            fqdn: fqdn("team.example.org."),
+            parent: zone_ref("example-org", "default"),
+        }
+    );
+
+    // Without a namespace, the zoneRef is relative to the zone's own namespace.
+    let relative = zone(
+        "team",
+        "team",
+        json!({ "domainName": "team", "zoneRef": { "name": "example-org" } }),
+    );
+    assert!(matches!(
+        resolve_zone(&relative, &zones),
+        Resolution::Unadopted { fqdn: None, .. }
+    ));
+
+    // Children of parents without an fqdn are detached until it is resolved.
+    let unresolved = [zone(
+        "default",
+        "example-org",
+        json!({ "domainName": "example.org." }),
+    )];
+    assert!(matches!(
+        resolve_zone(&team, &unresolved),
+        Resolution::Unadopted { fqdn: None, .. }
+    ));
+
+    // And so are children of parents referring to a zone which no longer adopts them.
+    let mut detached = zone(
+        "default",
+        "example-org",
+        json!({ "domainName": "example", "zoneRef": { "name": "org" } }),
+    );
+    detached.status = serde_json::from_value(json!({ "fqdn": "example.org." })).unwrap();
+    assert!(matches!(
+        resolve_zone(&team, &[detached]),
+        Resolution::Unadopted { fqdn: None, .. }
+    ));
+}
+
+#[test]
+fn invalid_zones_are_not_resolved() {
+    let both = zone(
+        "default",
+        "both",
+        json!({ "domainName": "both.example.org.", "zoneRef": { "name": "example-org" } }),
+    );
+    let neither = zone("default", "neither", json!({ "domainName": "neither" }));
+
+    for zone in [both, neither] {
+        assert!(matches!(
+            resolve_zone(&zone, &[example_org()]),
+            Resolution::Invalid { .. }
+        ));
+    }
+}
+
+#[test]
+fn children_are_found_by_label_or_zone_ref() {
+    let parent = example_org();
+
+    let labelled = adopted_record(
+        "default",
+        "www",
+        &parent,
+        json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert!(is_child(&labelled, None, &parent));
+
+    // Zone references default to the namespace of the child.
+    let referring = zone(
+        "default",
+        "sub",
+        json!({ "domainName": "sub", "zoneRef": { "name": "example-org" } }),
+    );
+    assert!(is_child(
+        &referring,
+        referring.spec.zone_ref.as_ref(),
+        &parent
+    ));
+
+    let other_namespace = zone(
+        "team",
+        "sub",
+        json!({ "domainName": "sub", "zoneRef": { "name": "example-org" } }),
+    );
+    assert!(!is_child(
+        &other_namespace,
+        other_namespace.spec.zone_ref.as_ref(),
+        &parent
+    ));
+
+    let unrelated = record(
+        "default",
+        "www",
+        json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert!(!is_child(&unrelated, None, &parent));
+}
+
+#[test]
+fn children_are_found_in_store() {
+    let parent = example_org();
+
+    let mut writer = Writer::<Zone>::default();
+    for zone in [
+        zone(
+            "default",
+            "sub",
+            json!({ "domainName": "sub", "zoneRef": { "name": "example-org" } }),
+        ),
+        sub_example_org(),
+        parent.clone(),
+    ] {
+        writer.apply_watcher_event(&watcher::Event::Apply(zone));
+    }
+
+    let children = children_in(&writer.as_reader(), &parent, |zone: &Zone| {
+        zone.spec.zone_ref.as_ref()
+    });
+    assert_eq!(children, [ObjectRef::new("sub").within("default")]);
+}
+
+#[test]
+fn zone_ref_cycles_are_detected() {
+    let a = zone(
+        "default",
+        "a",
+        json!({ "domainName": "a", "zoneRef": { "name": "b" } }),
+    );
+    let b = zone(
+        "default",
+        "b",
+        json!({ "domainName": "b", "zoneRef": { "name": "c" } }),
+    );
+    let c = zone(
+        "default",
+        "c",
+        json!({ "domainName": "c", "zoneRef": { "name": "a" } }),
+    );
+    let outside = zone(
+        "default",
+        "outside",
+        json!({ "domainName": "outside", "zoneRef": { "name": "a" } }),
+    );
+    let zones = [a.clone(), b, c, outside.clone()];
+
+    let cycle: Vec<_> = zone_ref_cycle(&outside, &zones)
+        .unwrap()
+        .iter()
+        .map(|zone| zone.name_any())
+        .collect();
+    assert_eq!(cycle, ["a", "b", "c"]);
+
+    assert_eq!(
+        resolve_zone(&a, &zones),
+        Resolution::Invalid {
+            reason: "zoneRefs form a cycle: default/a -> default/b -> default/c -> default/a"
+                .to_string()
+        }
+    );
+
+    // Zones leading into a cycle are not part of it, but cannot be adopted either.
+    assert!(matches!(
+        resolve_zone(&outside, &zones),
+        Resolution::Unadopted { fqdn: None, .. }
+    ));
+
+    // Stale fqdns within the cycle must not be used for adoption.
+    let resolved_b = resolved_zone(
+        "default",
+        "b",
+        json!({ "domainName": "b.example.org.", "zoneRef": { "name": "a" } }),
+    );
+    assert!(matches!(
+        resolve_zone(&a, &[a.clone(), resolved_b]),
+        Resolution::Invalid { .. }
+    ));
+
+    let unresolved = [example_org(), a.clone()];
+    assert!(zone_ref_cycle(&a, &unresolved).is_none());
+}
+
+#[test]
+fn records_are_adopted_by_longest_parent() {
+    let zones = [example_org(), sub_example_org()];
+
+    let www = record(
+        "default",
+        "www",
+        json!({ "domainName": "www.sub.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert_eq!(
+        resolve_record(&www, &zones),
+        Resolution::Adopted {
+            fqdn: fqdn("www.sub.example.org."),
+            parent: zone_ref("sub-example-org", "default"),
+        }
+    );
+
+    let orphan = record(
+        "default",
+        "orphan",
+        json!({ "domainName": "www.example.com.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert!(matches!(
+        resolve_record(&orphan, &zones),
+        Resolution::Unadopted { fqdn: Some(_), .. }
+    ));
+}
+
+#[test]
+fn record_delegations_are_enforced() {
+    let zones = [example_org()];
+
+    let allowed = record(
+        "team",
+        "team",
+        json!({ "domainName": "team.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert!(matches!(
+        resolve_record(&allowed, &zones),
+        Resolution::Adopted { .. }
+    ));
+
+    // Wrong type
+    let wrong_type = record(
+        "team",
+        "team",
+        json!({ "domainName": "team.example.org.", "type": "TXT", "rdata": "hello" }),
+    );
+    assert!(matches!(
+        resolve_record(&wrong_type, &zones),
+        Resolution::Unadopted { .. }
+    ));
+
+    // Wrong namespace
+    let wrong_namespace = record(
+        "other",
+        "team",
+        json!({ "domainName": "team.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert!(matches!(
+        resolve_record(&wrong_namespace, &zones),
+        Resolution::Unadopted { .. }
+    ));
+
+    // Relative record through zoneRef
+    let relative = record(
+        "team",
+        "team",
+        json!({
+            "domainName": "team",
+            "zoneRef": { "name": "example-org", "namespace": "default" },
+            "type": "A",
+            "rdata": "192.168.0.1",
+        }),
+    );
+    assert_eq!(
+        resolve_record(&relative, &zones),
+        Resolution::Adopted {
+            fqdn: fqdn("team.example.org."),
+            parent: zone_ref("example-org", "default"),
+        }
+    );
+}
+
+#[test]
+fn wildcard_records() {
+    let zones = [example_org()];
+
+    let a = record(
+        "default",
+        "apps",
+        json!({ "domainName": "*.apps.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    assert!(matches!(
+        resolve_record(&a, &zones),
+        Resolution::Adopted { .. }
+    ));
+
+    let ns = record(
+        "default",
+        "apps",
+        json!({ "domainName": "*.apps.example.org.", "type": "NS", "rdata": "ns.example.org." }),
+    );
+    assert!(matches!(
+        resolve_record(&ns, &zones),
+        Resolution::Unadopted { .. }
+    ));
+}
+
+#[test]
+fn entries_only_include_valid_children() {
+    let example_org = example_org();
+    let sub_example_org = sub_example_org();
+
+    let records = [
+        adopted_record(
+            "default",
+            "www",
+            &example_org,
+            json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1", "ttl": 60 }),
+        ),
+        // Child of another zone.
+        adopted_record(
+            "default",
+            "sub",
+            &sub_example_org,
+            json!({ "domainName": "www.sub.example.org.", "type": "A", "rdata": "192.168.0.2" }),
+        ),
+        // Claims to be a child, but is not covered by the delegations.
+        adopted_record(
+            "team",
+            "hijack",
+            &example_org,
+            json!({ "domainName": "www.example.org.", "type": "A", "rdata": "10.0.0.1" }),
+        ),
+        // Wildcard NS records are never allowed.
+        adopted_record(
+            "default",
+            "wildcard-ns",
+            &example_org,
+            json!({ "domainName": "*.example.org.", "type": "NS", "rdata": "ns.example.org." }),
+        ),
+    ];
+
+    let (entries, rejections) = zone_entries(&example_org, &records, TtlPolicy::Minimum);
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].fqdn, fqdn("www.example.org."));
+    assert_eq!(entries[0].ttl, 60);
+
+    assert_eq!(
+        rejections
+            .iter()
+            .map(|rejection| rejection.object.as_str())
+            .collect::<Vec<_>>(),
+        ["team/hijack", "default/wildcard-ns"]
+    );
+
+    // Records without a TTL inherit the zone's.
+    let (entries, _) = zone_entries(&sub_example_org, &records, TtlPolicy::Minimum);
+    assert_eq!(entries[0].ttl, sub_example_org.spec.ttl);
+}
+
+#[test]
+fn rrset_ttls_are_normalized() {
+    let example_org = example_org();
+
+    let created = |mut record: Record, timestamp: &str| {
+        record.metadata.creation_timestamp = serde_json::from_value(json!(timestamp)).unwrap();
+        record
+    };
+
+    let records = [
+        created(
+            adopted_record(
+                "default",
+                "www-1",
+                &example_org,
+                json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1", "ttl": 300 }),
+            ),
+            "2024-05-02T12:00:00Z",
+        ),
+        created(
+            adopted_record(
+                "default",
+                "www-2",
+                &example_org,
+                json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.2", "ttl": 60 }),
+            ),
+            "2024-05-03T12:00:00Z",
+        ),
+        // Inherits the zone's TTL, and was created first.
+        created(
+            adopted_record(
+                "default",
+                "www-3",
+                &example_org,
+                json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.3" }),
+            ),
+            "2024-05-01T12:00:00Z",
+        ),
+        // Different type, so not part of the same RRset.
+        adopted_record(
+            "default",
+            "www-aaaa",
+            &example_org,
+            json!({ "domainName": "www.example.org.", "type": "AAAA", "rdata": "::1", "ttl": 30 }),
+        ),
+    ];
+
+    let ttls = |policy| {
+        zone_entries(&example_org, &records, policy)
+            .0
+            .iter()
+            .map(|entry| entry.ttl)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ttls(TtlPolicy::Minimum), [60, 60, 60, 30]);
+    assert_eq!(ttls(TtlPolicy::Maximum), [360, 360, 360, 30]);
+    assert_eq!(ttls(TtlPolicy::FirstCreated), [360, 360, 360, 30]);
+
+    let normalized = |policy| {
+        let (entries, _) = zone_entries(&example_org, &records, policy);
+        let mut normalized = normalized_ttls(&example_org, &records, &entries)
+            .into_iter()
+            .map(|(record, ttl)| (record.name, ttl))
+            .collect::<Vec<_>>();
+
+        normalized.sort();
+        normalized
+    };
+
+    assert_eq!(
+        normalized(TtlPolicy::Minimum),
+        [("www-1".to_string(), 60), ("www-3".to_string(), 60)]
+    );
+    assert_eq!(
+        normalized(TtlPolicy::FirstCreated),
+        [("www-1".to_string(), 360), ("www-2".to_string(), 360)]
+    );
+}
+
+#[test]
+fn publications_track_record_generations() {
+    let example_org = example_org();
+
+    let mut www = adopted_record(
+        "default",
+        "www",
+        &example_org,
+        json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+    );
+    www.metadata.generation = Some(1);
+
+    let records = [
+        www.clone(),
+        adopted_record(
+            "team",
+            "hijack",
+            &example_org,
+            json!({ "domainName": "www.example.org.", "type": "A", "rdata": "10.0.0.1" }),
+        ),
+    ];
+
+    // Rejected records are never reported as published.
+    assert_eq!(
+        published_records(&example_org, &records)
+            .iter()
+            .map(|record| record.name_any())
+            .collect::<Vec<_>>(),
+        ["www"]
+    );
+
+    let published = publication(&www, &example_org, 2024050100).unwrap();
+    assert_eq!(
+        published,
+        Publication {
+            observed_generation: Some(1),
+            zone: example_org.zone_ref().as_label(),
+            fqdn: fqdn("www.example.org."),
+            serial: 2024050100,
+        }
+    );
+
+    www.annotations_mut().insert(
+        PUBLISHED_ANNOTATION.to_string(),
+        serde_json::to_string(&published).unwrap(),
+    );
+    assert_eq!(Publication::of(&www), Some(published));
+
+    // Later serials of the zone do not change the serial the record was published at.
+    assert_eq!(publication(&www, &example_org, 2024050101), None);
+
+    // But a new generation does.
+    www.metadata.generation = Some(2);
+    assert_eq!(
+        publication(&www, &example_org, 2024050102).map(|published| published.serial),
+        Some(2024050102)
+    );
+
+    // Unless it changed the domain name, and is still published under its previous fqdn.
+    www.metadata.generation = Some(3);
+    www.spec.domain_name = DomainName::try_from("web.example.org.").unwrap();
+    assert_eq!(publication(&www, &example_org, 2024050103), None);
+}
+
+#[test]
+fn flattened_zones_include_descendant_entries() {
+    let mut example_org = zone(
+        "default",
+        "example-org",
+        json!({
+            "domainName": "example.org.",
+            "delegations": [{ "records": [{ "pattern": "*" }], "zones": ["*"] }],
+        }),
+    );
+    example_org.metadata.annotations =
+        Some([(FLATTEN_ANNOTATION.to_string(), "true".to_string())].into());
+
+    let mut snapshot = Snapshot {
+        zones: vec![
+            example_org,
+            zone(
+                "default",
+                "sub-example-org",
+                json!({
+                    "domainName": "sub.example.org.",
+                    "delegations": [{ "records": [{ "pattern": "*" }], "zones": ["deep"] }],
+                }),
+            ),
+            zone(
+                "default",
+                "deep-sub-example-org",
+                json!({
+                    "domainName": "deep.sub.example.org.",
+                    "ttl": 900,
+                    "delegations": [{ "records": [{ "pattern": "*" }] }],
+                }),
+            ),
+        ],
+        records: vec![
+            record(
+                "default",
+                "www",
+                json!({ "domainName": "www.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+            ),
+            // Delegation of the flattened child zone.
+            record(
+                "default",
+                "sub-ns",
+                json!({ "domainName": "sub.example.org.", "type": "NS", "rdata": "ns.example.net." }),
+            ),
+            record(
+                "default",
+                "www-sub",
+                json!({ "domainName": "www.sub.example.org.", "type": "A", "rdata": "192.168.0.2", "ttl": 60 }),
+            ),
+            record(
+                "default",
+                "api-deep",
+                json!({ "domainName": "api.deep.sub.example.org.", "type": "A", "rdata": "192.168.0.3" }),
+            ),
+            // Published by the zone itself, within the same RRset as a flattened record.
+            record(
+                "default",
+                "www-sub-backup",
+                json!({ "domainName": "www.sub", "zoneRef": { "name": "example-org" }, "type": "A", "rdata": "192.168.0.4" }),
+            ),
+        ],
+        ttl_policy: TtlPolicy::Minimum,
+        soa: SoaDefaults::default(),
+    };
+
+    assert!(snapshot.resolve(noon(2024, 17)).is_empty());
+
+    let entries: Vec<_> = snapshot.zones[0]
+        .status
+        .as_ref()
+        .unwrap()
+        .entries
+        .iter()
+        .map(|entry| (entry.fqdn.to_string(), entry.type_, entry.ttl))
+        .collect();
+
+    assert_eq!(
+        entries,
+        [
+            ("example.org.".to_string(), Type::SOA, 360),
+            ("www.example.org.".to_string(), Type::A, 360),
+            // RRsets spanning flattened zones share a single TTL.
+            ("www.sub.example.org.".to_string(), Type::A, 60),
+            ("www.sub.example.org.".to_string(), Type::A, 60),
+            // TTLs inherited from flattened zones are preserved.
+            ("api.deep.sub.example.org.".to_string(), Type::A, 900),
+        ]
+    );
+
+    let normalized = normalized_ttls(
+        &snapshot.zones[0],
+        &snapshot.records,
+        &snapshot.zones[0].status.as_ref().unwrap().entries,
+    );
+    assert_eq!(
+        normalized
+            .into_iter()
+            .map(|(record, ttl)| (record.name, ttl))
+            .collect::<Vec<_>>(),
+        [("www-sub-backup".to_string(), 60)]
+    );
+
+    // Child zones keep publishing their own entries.
+    assert!(snapshot.zones[1]
+        .status
+        .as_ref()
+        .unwrap()
+        .entries
+        .iter()
+        .any(|entry| entry.type_ == Type::SOA));
+}
+
+#[test]
+fn serials() {
+    let mut zone = example_org();
+    let hash = zone_hash(&zone, &[]);
+
+    // New zones start out with today's date.
+    assert_eq!(next_serial(&zone, &hash, noon(2024, 17)), 2024051700);
+
+    // Unchanged zones keep their serial.
+    let status = zone.status.as_mut().unwrap();
+    status.hash = Some(hash.clone());
+    status.serial = Some(2024051703);
+    assert_eq!(next_serial(&zone, &hash, noon(2024, 18)), 2024051703);
+
+    // Changed zones are incremented within the same day, and reset on the next.
+    assert_eq!(next_serial(&zone, "changed", noon(2024, 17)), 2024051704);
+    assert_eq!(next_serial(&zone, "changed", noon(2024, 18)), 2024051800);
+}
+
+#[test]
+fn status_starts_with_soa() {
+    let zone = example_org();
+
+    let (status, _) = zone_status(
+        &zone,
+        &[],
+        &[],
+        TtlPolicy::Minimum,
+        &SoaDefaults::default(),
+        noon(2024, 17),
+    )
+    .unwrap();
+    assert_eq!(status.serial, Some(2024051700));
+    assert_eq!(status.entries.len(), 1);
+    assert_eq!(status.entries[0].type_, Type::SOA);
+    assert!(status.entries[0].rdata.contains("2024051700"));
+    assert!(status.entries[0]
+        .rdata
+        .starts_with("ns.example.org. noc.example.org. "));
+
+    let unresolved = self::zone(
+        "default",
+        "example-org",
+        json!({ "domainName": "example.org." }),
+    );
+    assert!(zone_status(
+        &unresolved,
+        &[],
+        &[],
+        TtlPolicy::Minimum,
+        &SoaDefaults::default(),
+        noon(2024, 17)
+    )
+    .is_none());
+}
+
+#[test]
+fn soa_defaults_are_published() {
+    let zone = example_org();
+    let soa = SoaDefaults {
+        nameserver: Some(fqdn("ns1.example.net.")),
+        mailbox: Some(fqdn("hostmaster.example.net.")),
+    };
+
+    let (status, _) =
+        zone_status(&zone, &[], &[], TtlPolicy::Minimum, &soa, noon(2024, 17)).unwrap();
+    assert!(status.entries[0]
+        .rdata
+        .starts_with("ns1.example.net. hostmaster.example.net. (2024051700 "));
+}
+
+#[test]
+fn snapshot_converges() {
+    // Listed in reverse order of dependence, so resolving
+    // the chain takes multiple passes.
+    let mut snapshot = Snapshot {
+        zones: vec![
+            zone(
+                "default",
+                "deep",
+                json!({
+                    "domainName": "deep",
+                    "zoneRef": { "name": "sub" },
+                    "delegations": [{ "records": [{ "pattern": "*" }] }],
+                }),
+            ),
+            zone(
+                "default",
+                "sub",
+                json!({
+                    "domainName": "sub",
+                    "zoneRef": { "name": "example-org" },
+                    "delegations": [{ "zones": ["deep"] }],
+                }),
+            ),
+            zone(
+                "default",
+                "example-org",
+                json!({
+                    "domainName": "example.org.",
+                    "delegations": [{ "zones": ["sub"] }],
+                }),
+            ),
+        ],
+        records: vec![record(
+            "default",
+            "www",
+            json!({ "domainName": "www.deep.sub.example.org.", "type": "A", "rdata": "192.168.0.1" }),
+        )],
+        ttl_policy: TtlPolicy::Minimum,
+        soa: SoaDefaults::default(),
+    };
+
+    let rejections = snapshot.resolve(noon(2024, 17));
+    assert!(rejections.is_empty(), "{rejections:?}");
+
+    let deep = &snapshot.zones[0];
+    assert_eq!(deep.fqdn(), Some(&fqdn("deep.sub.example.org.")));
+    assert_eq!(deep.parent(), Some(zone_ref("sub", "default")));
+
+    let www = &snapshot.records[0];
+    assert_eq!(www.parent(), Some(zone_ref("deep", "default")));
+
+    let entries = &deep.status.as_ref().unwrap().entries;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].fqdn, fqdn("www.deep.sub.example.org."));
+}
+
+#[test]
+fn snapshot_detaches_unadopted_zones_and_records() {
+    let example_org = example_org();
+
+    // Previously adopted by example.org., which no longer delegates either of them.
+    let mut stale = resolved_zone(
+        "default",
+        "stale",
+        json!({ "domainName": "stale.example.org." }),
+    );
+    set_parent_label(&mut stale, &example_org);
+
+    let mut referenced = zone(
+        "default",
+        "referenced",
+        json!({ "domainName": "referenced", "zoneRef": { "name": "example-org" } }),
+    );
+    referenced.status = serde_json::from_value(json!({
+        "fqdn": "referenced.example.org.",
+        "serial": 2024051700,
+        "entries": [{
+            "fqdn": "referenced.example.org.",
+            "type": "SOA",
+            "class": "IN",
+            "ttl": 360,
+            "rdata": "ns.referenced.example.org. noc.referenced.example.org. 2024051700 86400 7200 3600000 360",
+        }],
+    }))
+    .unwrap();
+    set_parent_label(&mut referenced, &example_org);
+
+    // Adopted by the zone above, which is itself detached.
+    let mut deep = zone(
+        "default",
+        "deep",
+        json!({ "domainName": "deep", "zoneRef": { "name": "referenced" } }),
+    );
+    deep.status =
+        serde_json::from_value(json!({ "fqdn": "deep.referenced.example.org." })).unwrap();
+    set_parent_label(&mut deep, &referenced);
+
+    // Records in the other namespace are not delegated by example.org.
+    let mut record = record(
+        "other",
+        "www",
+        json!({
+            "domainName": "www",
+            "zoneRef": { "name": "example-org", "namespace": "default" },
+            "type": "A",
+            "rdata": "127.0.0.1",
+        }),
+    );
+    record.status = serde_json::from_value(json!({ "fqdn": "www.example.org." })).unwrap();
+    set_parent_label(&mut record, &example_org);
+
+    let mut snapshot = Snapshot {
+        zones: vec![example_org, stale, referenced, deep],
+        records: vec![record],
+        ..Default::default()
+    };
+
+    let rejections = snapshot.resolve(noon(2024, 17));
+    assert_eq!(rejections.len(), 4, "{rejections:?}");
+
+    let stale = &snapshot.zones[1];
+    assert_eq!(stale.parent(), None);
+    assert_eq!(stale.fqdn(), Some(&fqdn("stale.example.org.")));
+
+    // Zones with a zoneRef lose the fqdn derived from the parent as well,
+    // and no longer publish any entries.
+    let referenced = &snapshot.zones[2];
+    assert_eq!(referenced.parent(), None);
+    assert!(referenced.status.is_none());
+
+    // Which detaches the zones referring to them in turn.
+    let deep = &snapshot.zones[3];
+    assert_eq!(deep.parent(), None);
+    assert!(deep.status.is_none());
+
+    // And so do records.
+    let record = &snapshot.records[0];
+    assert_eq!(record.parent(), None);
+    assert_eq!(record.fqdn(), None);
+}
+
+fn set_parent_label<K: Resource>(resource: &mut K, parent: &Zone) {
+    resource.meta_mut().labels =
+        Some([(PARENT_ZONE_LABEL.to_string(), parent.zone_ref().as_label())].into());
+}
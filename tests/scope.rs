@@ -0,0 +1,150 @@
+use futures::{executor::block_on, stream, StreamExt};
+use k8s_openapi::serde_json::{self, json};
+use kube::{runtime::watcher::Event, ResourceExt as _};
+use kubizone::{merge_watches, without_relists, Scope};
+use kubizone_crds::v1alpha1::Record;
+
+fn record(name: &str) -> Record {
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Record",
+        "metadata": { "name": name, "namespace": "default" },
+        "spec": { "domainName": format!("{name}.example.org."), "type": "A", "rdata": "127.0.0.1" },
+    }))
+    .unwrap()
+}
+
+fn describe(events: Vec<Event<Record>>) -> Vec<String> {
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Apply(record) => format!("apply {}", record.name_any()),
+            Event::Delete(record) => format!("delete {}", record.name_any()),
+            Event::Init => "init".to_string(),
+            Event::InitApply(record) => format!("init-apply {}", record.name_any()),
+            Event::InitDone => "init-done".to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn relists_are_translated_into_applies_and_deletes() {
+    let events = stream::iter(
+        [
+            Event::Init,
+            Event::InitApply(record("a")),
+            Event::InitApply(record("b")),
+            Event::InitDone,
+            Event::Apply(record("c")),
+            Event::Delete(record("c")),
+            // The watch was restarted, during which `a` was deleted.
+            Event::Init,
+            Event::InitApply(record("b")),
+            Event::InitDone,
+        ]
+        .map(Ok),
+    );
+
+    let events = block_on(
+        without_relists(events)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>(),
+    );
+
+    assert_eq!(
+        describe(events),
+        ["apply a", "apply b", "apply c", "delete c", "apply b", "delete a"]
+    );
+}
+
+#[test]
+fn merged_watches_are_listed_once_all_namespaces_are() {
+    let first = stream::iter(
+        [
+            Event::Init,
+            Event::InitApply(record("a")),
+            Event::InitDone,
+            Event::Apply(record("c")),
+        ]
+        .map(Ok),
+    );
+
+    let second =
+        stream::iter([Event::Init, Event::InitApply(record("b")), Event::InitDone].map(Ok));
+
+    let events = block_on(
+        merge_watches(vec![first.boxed(), second.boxed()])
+            .map(Result::unwrap)
+            .collect::<Vec<_>>(),
+    );
+
+    let events = describe(events);
+    let start = events.iter().position(|event| event == "init").unwrap();
+    let end = events
+        .iter()
+        .position(|event| event == "init-done")
+        .unwrap();
+
+    // Objects of both namespaces are included in a single listing,
+    // which only completes once both namespaces were listed.
+    assert!(events[start..end].contains(&"init-apply a".to_string()));
+    assert!(events[start..end].contains(&"init-apply b".to_string()));
+    assert!(events[..start].contains(&"apply b".to_string()));
+    assert_eq!(events.iter().filter(|event| *event == "init").count(), 1);
+}
+
+#[test]
+fn shards_have_their_own_field_manager() {
+    let scope = Scope {
+        shard: Some("a".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        scope.field_manager("kubi.zone/zone-resolver"),
+        "kubi.zone/zone-resolver-a"
+    );
+    assert_eq!(
+        Scope::default().field_manager("kubi.zone/zone-resolver"),
+        "kubi.zone/zone-resolver"
+    );
+}
+
+#[test]
+fn selectors_restrict_watches_and_lists() {
+    let scope = Scope {
+        zone_selector: Some("shard=a".to_string()),
+        record_selector: Some("shard=b".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        scope.zone_config().label_selector.as_deref(),
+        Some("shard=a")
+    );
+    assert_eq!(
+        scope.zone_params().label_selector.as_deref(),
+        Some("shard=a")
+    );
+    assert_eq!(
+        scope.record_config().label_selector.as_deref(),
+        Some("shard=b")
+    );
+
+    // Selectors of the caller are combined with the record selector of the scope.
+    assert_eq!(
+        scope
+            .record_params(Some("app=web"))
+            .label_selector
+            .as_deref(),
+        Some("shard=b,app=web")
+    );
+    assert_eq!(
+        Scope::default()
+            .record_params(Some("app=web"))
+            .label_selector
+            .as_deref(),
+        Some("app=web")
+    );
+    assert_eq!(Scope::default().record_params(None).label_selector, None);
+}
@@ -0,0 +1,131 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone::Scope;
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        let scope = Scope {
+            zone_selector: Some("kubizone-shard=a".to_string()),
+            record_selector: Some("kubizone-shard=a".to_string()),
+            shard: Some("a".to_string()),
+            ..Default::default()
+        };
+
+        crate::common::run_scoped(scope, async move |ctx: Context| {
+            ctx.namespace("kubizone-sharding").await.unwrap();
+
+            let delegation = Delegation {
+                namespaces: vec![],
+                records: vec![RecordDelegation {
+                    pattern: Pattern::try_from("*").unwrap(),
+                    types: vec![],
+                }],
+                zones: vec![],
+            };
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-sharding",
+                    "example-org",
+                    "example.org.",
+                    std::slice::from_ref(&delegation),
+                )
+                .await
+                .unwrap();
+            let example_org = ctx
+                .label(&example_org, "kubizone-shard", "a")
+                .await
+                .unwrap();
+
+            let example_net = ctx
+                .zone(
+                    "kubizone-sharding",
+                    "example-net",
+                    "example.net.",
+                    &[delegation],
+                )
+                .await
+                .unwrap();
+            let example_net = ctx
+                .label(&example_net, "kubizone-shard", "b")
+                .await
+                .unwrap();
+
+            let good_example_org = ctx
+                .a_record("kubizone-sharding", "good-example-org", "good.example.org.")
+                .await
+                .unwrap();
+            let good_example_org = ctx
+                .label(&good_example_org, "kubizone-shard", "a")
+                .await
+                .unwrap();
+
+            // Records of other shards are never touched.
+            let other_example_org = ctx
+                .a_record(
+                    "kubizone-sharding",
+                    "other-example-org",
+                    "other.example.org.",
+                )
+                .await
+                .unwrap();
+            let other_example_org = ctx
+                .label(&other_example_org, "kubizone-shard", "b")
+                .await
+                .unwrap();
+
+            // Records are only resolved against zones of their own shard.
+            let good_example_net = ctx
+                .a_record("kubizone-sharding", "good-example-net", "good.example.net.")
+                .await
+                .unwrap();
+            let good_example_net = ctx
+                .label(&good_example_net, "kubizone-shard", "a")
+                .await
+                .unwrap();
+
+            ctx.wait_for(&good_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[
+                    has_serial(),
+                    has_entry("good.example.org."),
+                    not(has_entry("other.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &other_example_org,
+                &[not(has_fqdn()), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &good_example_net,
+                &[has_fqdn(), not(has_parent(&example_net))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(&example_net, &[not(has_serial())])
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+}
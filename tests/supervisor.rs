@@ -0,0 +1,103 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use kubizone::supervisor::{supervise, RestartPolicy, Shutdown, Unrecoverable};
+
+fn policy() -> RestartPolicy {
+    RestartPolicy {
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_secs(60),
+        max_restarts: 3,
+    }
+}
+
+#[test]
+fn backoff_doubles_up_to_limit() {
+    let policy = RestartPolicy {
+        initial_backoff: Duration::from_secs(1),
+        max_backoff: Duration::from_secs(10),
+        max_restarts: 100,
+    };
+
+    assert_eq!(policy.backoff(1), Duration::from_secs(1));
+    assert_eq!(policy.backoff(2), Duration::from_secs(2));
+    assert_eq!(policy.backoff(4), Duration::from_secs(8));
+    assert_eq!(policy.backoff(5), Duration::from_secs(10));
+    assert_eq!(policy.backoff(100), Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn failing_controller_is_restarted_until_unrecoverable() {
+    let runs = Arc::new(AtomicU32::new(0));
+
+    let outcome = supervise("test", policy(), Shutdown::default(), || {
+        let runs = runs.clone();
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            panic!("controller failed");
+        }
+    })
+    .await;
+
+    assert_eq!(
+        outcome,
+        Err(Unrecoverable {
+            name: "test",
+            failures: 4
+        })
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn recovered_controller_keeps_running() {
+    let runs = Arc::new(AtomicU32::new(0));
+    let shutdown = Shutdown::default();
+
+    let outcome = supervise("test", policy(), shutdown.clone(), || {
+        let runs = runs.clone();
+        let shutdown = shutdown.clone();
+        async move {
+            // Exit unexpectedly twice, then run until shut down.
+            if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                return;
+            }
+
+            shutdown.trigger();
+        }
+    })
+    .await;
+
+    assert_eq!(outcome, Ok(()));
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_controller() {
+    let shutdown = Shutdown::default();
+    let drained = Arc::new(AtomicU32::new(0));
+
+    let supervisor = tokio::spawn(supervise("test", policy(), shutdown.clone(), {
+        let shutdown = shutdown.clone();
+        let drained = drained.clone();
+        move || {
+            let shutdown = shutdown.clone();
+            let drained = drained.clone();
+            async move {
+                shutdown.wait().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drained.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }));
+
+    shutdown.trigger();
+
+    assert_eq!(supervisor.await.unwrap(), Ok(()));
+    assert_eq!(drained.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,77 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use kubizone::telemetry::{init, LogFormat};
+use tracing::info;
+
+/// Minimal OTLP/HTTP collector, accepting a single export
+/// request and passing back its path and body.
+fn collector() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let path = request_line.split(' ').nth(1).unwrap().to_string();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+        sender.send((path, body)).unwrap();
+    });
+
+    (endpoint, receiver)
+}
+
+#[test]
+fn spans_are_exported() {
+    let (endpoint, requests) = collector();
+    let telemetry = init(LogFormat::Json, Some(&endpoint)).unwrap();
+
+    tracing::info_span!(
+        "zone",
+        kind = "Zone",
+        namespace = "default",
+        object = "example-org"
+    )
+    .in_scope(|| info!("reconciling"));
+
+    // Flushes the pending spans.
+    drop(telemetry);
+
+    let (path, body) = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(path, "/v1/traces");
+
+    let contains = |needle: &[u8]| body.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"kubizone"));
+    assert!(contains(b"zone"));
+    assert!(contains(b"example-org"));
+}
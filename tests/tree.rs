@@ -0,0 +1,114 @@
+use k8s_openapi::serde_json::{self, json};
+use kubizone::tree::build;
+use kubizone_crds::{
+    v1alpha1::{Record, Zone},
+    PARENT_ZONE_LABEL,
+};
+
+fn zone(name: &str, fqdn: &str, parent: Option<&str>) -> Zone {
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Zone",
+        "metadata": {
+            "name": name,
+            "namespace": "default",
+            "uid": name,
+            "labels": parent
+                .map(|parent| json!({ PARENT_ZONE_LABEL: parent }))
+                .unwrap_or(json!({})),
+        },
+        "spec": {
+            "domainName": fqdn,
+            "delegations": [{ "records": [{ "pattern": "*" }], "zones": ["*"] }],
+        },
+        "status": { "fqdn": fqdn, "serial": 2024051700, "entries": [] },
+    }))
+    .unwrap()
+}
+
+fn record(name: &str, fqdn: &str, parent: Option<&str>) -> Record {
+    serde_json::from_value(json!({
+        "apiVersion": "kubi.zone/v1alpha1",
+        "kind": "Record",
+        "metadata": {
+            "name": name,
+            "namespace": "default",
+            "labels": parent
+                .map(|parent| json!({ PARENT_ZONE_LABEL: parent }))
+                .unwrap_or(json!({})),
+        },
+        "spec": { "domainName": fqdn, "type": "A", "rdata": "192.168.0.1" },
+        "status": { "fqdn": fqdn },
+    }))
+    .unwrap()
+}
+
+#[test]
+fn hierarchy_follows_parent_labels() {
+    let zones = [
+        zone("sub", "sub.example.org.", Some("example-org.default")),
+        zone("example-org", "example.org.", None),
+    ];
+    let records = [
+        record("www", "www.example.org.", Some("example-org.default")),
+        record("www-sub", "www.sub.example.org.", Some("sub.default")),
+    ];
+
+    let tree = build(&zones, &records);
+
+    assert_eq!(tree.zones.len(), 1);
+    assert!(tree.unadopted.is_empty());
+    assert!(tree.orphaned.is_empty());
+
+    let root = &tree.zones[0];
+    assert_eq!(root.zone, "default/example-org");
+    assert_eq!(root.serial, Some(2024051700));
+    assert_eq!(root.zones[0].zone, "default/sub");
+    assert_eq!(root.zones[0].records[0].record, "default/www-sub");
+    assert_eq!(root.records[0].record, "default/www");
+
+    assert_eq!(
+        tree.to_string(),
+        "example.org. (default/example-org) serial 2024051700, 0 entries\n\
+         ├── sub.example.org. (default/sub) serial 2024051700, 0 entries\n\
+         │   └── A www.sub.example.org. (default/www-sub)\n\
+         └── A www.example.org. (default/www)\n"
+    );
+
+    let json = serde_json::to_value(&tree).unwrap();
+    assert_eq!(json["zones"][0]["fqdn"], "example.org.");
+    assert_eq!(json["zones"][0]["zones"][0]["records"][0]["type"], "A");
+}
+
+#[test]
+fn detached_objects_are_listed() {
+    let zones = [
+        zone("example-org", "example.org.", None),
+        // Has not been labelled by the controller yet.
+        zone("sub", "sub.example.org.", None),
+        // Parent no longer exists.
+        zone("other", "other.example.com.", Some("example-com.default")),
+        // Parent labels pointing at each other.
+        zone("a", "a.example.net.", Some("b.default")),
+        zone("b", "b.example.net.", Some("a.default")),
+    ];
+    let records = [record("www", "www.example.com.", None)];
+
+    let tree = build(&zones, &records);
+
+    assert_eq!(tree.zones.len(), 1);
+
+    let unadopted: Vec<_> = tree
+        .unadopted
+        .iter()
+        .map(|detached| detached.object.as_str())
+        .collect();
+    assert_eq!(unadopted, ["default/sub", "default/www"]);
+
+    let orphaned: Vec<_> = tree
+        .orphaned
+        .iter()
+        .map(|detached| detached.object.as_str())
+        .collect();
+    assert_eq!(orphaned, ["default/other", "default/a", "default/b"]);
+}
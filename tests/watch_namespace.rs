@@ -0,0 +1,132 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use k8s_openapi::serde_json::json;
+    use kube::{
+        api::{Patch, PatchParams},
+        Api, ResourceExt,
+    };
+    use kubizone::Scope;
+    use kubizone_common::Pattern;
+    use kubizone_crds::{
+        v1alpha1::{Delegation, Record, RecordDelegation},
+        PARENT_ZONE_LABEL,
+    };
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        let scope = Scope {
+            namespaces: vec!["kubizone-watched-namespace".to_string()],
+            ..Default::default()
+        };
+
+        crate::common::run_scoped(scope, async move |ctx: Context| {
+            ctx.namespace("kubizone-watched-namespace").await.unwrap();
+            ctx.namespace("kubizone-unwatched-namespace").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-watched-namespace",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        namespaces: vec![],
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            types: vec![],
+                        }],
+                        zones: vec![],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let good_example_org = ctx
+                .a_record(
+                    "kubizone-watched-namespace",
+                    "good-example-org",
+                    "good.example.org.",
+                )
+                .await
+                .unwrap();
+
+            // Records outside the watched namespaces are never touched.
+            let bad_example_org = ctx
+                .a_record(
+                    "kubizone-unwatched-namespace",
+                    "bad-example-org",
+                    "bad.example.org.",
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&good_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[
+                    has_serial(),
+                    has_entry("good.example.org."),
+                    not(has_entry("bad.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &bad_example_org,
+                &[not(has_fqdn()), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            // Records referring to zones outside the watched namespaces lose any
+            // parent they were previously assigned.
+            let unwatched_org = ctx
+                .zone(
+                    "kubizone-unwatched-namespace",
+                    "unwatched-org",
+                    "unwatched.org.",
+                    &[],
+                )
+                .await
+                .unwrap();
+
+            let stale = ctx
+                .a_record_with_ref(
+                    "kubizone-watched-namespace",
+                    "stale",
+                    "stale",
+                    &unwatched_org,
+                )
+                .await
+                .unwrap();
+
+            Api::<Record>::namespaced(ctx.client().await, "kubizone-watched-namespace")
+                .patch_metadata(
+                    &stale.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "labels": { PARENT_ZONE_LABEL: example_org.zone_ref().as_label() }
+                        }
+                    })),
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&stale, &[not(has_parent(&example_org))])
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+}
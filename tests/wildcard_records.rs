@@ -0,0 +1,105 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone_common::{Pattern, Type};
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-wildcard-records").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-wildcard-records",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        records: vec![
+                            RecordDelegation {
+                                pattern: Pattern::try_from("*.apps").unwrap(),
+                                types: vec![],
+                            },
+                            RecordDelegation {
+                                pattern: Pattern::try_from("www").unwrap(),
+                                types: vec![],
+                            },
+                        ],
+                        namespaces: vec![],
+                        zones: vec![],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let wildcard_apps_example_org = ctx
+                .a_record(
+                    "kubizone-wildcard-records",
+                    "wildcard-apps-example-org",
+                    "*.apps.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let wildcard_example_org = ctx
+                .a_record(
+                    "kubizone-wildcard-records",
+                    "wildcard-example-org",
+                    "*.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let wildcard_ns_apps_example_org = ctx
+                .record(
+                    "kubizone-wildcard-records",
+                    "wildcard-ns-apps-example-org",
+                    "*.apps.example.org.",
+                    Type::NS,
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &wildcard_apps_example_org,
+                &[has_fqdn(), has_parent(&example_org)],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &example_org,
+                &[
+                    has_serial(),
+                    has_entry("*.apps.example.org."),
+                    not(has_entry("*.example.org.")),
+                ],
+            )
+            .await
+            .unwrap();
+
+            // The www delegation must not allow a wildcard which would also answer for www.
+            ctx.wait_for(
+                &wildcard_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            // NS records at wildcard owner names have undefined semantics.
+            ctx.wait_for(
+                &wildcard_ns_apps_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use json_patch::{PatchOperation, RemoveOperation};
+    use kube::{
+        api::{Patch, PatchParams},
+        Api, ResourceExt,
+    };
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation, Zone};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-zone-delegation-withdrawn")
+                .await
+                .unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-zone-delegation-withdrawn",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            types: vec![],
+                        }],
+                        namespaces: vec![],
+                        zones: vec![
+                            Pattern::try_from("sub").unwrap(),
+                            Pattern::try_from("ref").unwrap(),
+                        ],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let sub_example_org = ctx
+                .zone(
+                    "kubizone-zone-delegation-withdrawn",
+                    "sub-example-org",
+                    "sub.example.org.",
+                    &[],
+                )
+                .await
+                .unwrap();
+
+            let ref_example_org = ctx
+                .zone_with_ref(
+                    "kubizone-zone-delegation-withdrawn",
+                    "ref-example-org",
+                    "ref",
+                    &example_org,
+                    &[Delegation {
+                        records: vec![],
+                        namespaces: vec![],
+                        zones: vec![Pattern::try_from("deep").unwrap()],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let deep_ref_example_org = ctx
+                .zone_with_ref(
+                    "kubizone-zone-delegation-withdrawn",
+                    "deep-ref-example-org",
+                    "deep",
+                    &ref_example_org,
+                    &[],
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .a_record(
+                    "kubizone-zone-delegation-withdrawn",
+                    "www-example-org",
+                    "www.example.org.",
+                )
+                .await
+                .unwrap();
+
+            let ref_www_example_org = ctx
+                .a_record_with_ref(
+                    "kubizone-zone-delegation-withdrawn",
+                    "ref-www-example-org",
+                    "ref-www",
+                    &example_org,
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&sub_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&ref_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &deep_ref_example_org,
+                &[has_fqdn(), has_parent(&ref_example_org)],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(&www_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(
+                &ref_www_example_org,
+                &[has_fqdn(), has_parent(&example_org)],
+            )
+            .await
+            .unwrap();
+
+            let api =
+                Api::<Zone>::namespaced(ctx.client().await, "kubizone-zone-delegation-withdrawn");
+
+            // Delete the zone delegation
+            api.patch(
+                &example_org.name_any(),
+                &PatchParams::apply("zone-delegation-withdrawn"),
+                &Patch::<Zone>::Json(json_patch::Patch(vec![PatchOperation::Remove(
+                    RemoveOperation {
+                        path: jsonptr::Pointer::new(["spec", "delegations", "0"]),
+                    },
+                )])),
+            )
+            .await
+            .unwrap();
+
+            // The fully qualified zone and record keep their fqdn, while
+            // the ones referring to their former parent lose it.
+            ctx.wait_for(
+                &sub_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &ref_example_org,
+                &[
+                    not(has_fqdn()),
+                    not(has_serial()),
+                    not(has_parent(&example_org)),
+                ],
+            )
+            .await
+            .unwrap();
+
+            // Which in turn detaches the zone referring to it.
+            ctx.wait_for(
+                &deep_ref_example_org,
+                &[not(has_fqdn()), not(has_parent(&ref_example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &www_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &ref_www_example_org,
+                &[not(has_fqdn()), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(feature = "dev")]
+mod common;
+
+#[cfg(feature = "dev")]
+mod tests {
+    use kubizone_common::Pattern;
+    use kubizone_crds::v1alpha1::{Delegation, RecordDelegation};
+    use serial_test::serial;
+
+    use crate::common::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn main() {
+        crate::common::run(async move |ctx: Context| {
+            ctx.namespace("kubizone-zone-deletion").await.unwrap();
+
+            let example_org = ctx
+                .zone(
+                    "kubizone-zone-deletion",
+                    "example-org",
+                    "example.org.",
+                    &[Delegation {
+                        records: vec![RecordDelegation {
+                            pattern: Pattern::try_from("*").unwrap(),
+                            types: vec![],
+                        }],
+                        namespaces: vec![],
+                        zones: vec![Pattern::try_from("*").unwrap()],
+                    }],
+                )
+                .await
+                .unwrap();
+
+            let sub_example_org = ctx
+                .zone_with_ref(
+                    "kubizone-zone-deletion",
+                    "sub-example-org",
+                    "sub",
+                    &example_org,
+                    &[],
+                )
+                .await
+                .unwrap();
+
+            let www_example_org = ctx
+                .a_record_with_ref(
+                    "kubizone-zone-deletion",
+                    "www-example-org",
+                    "www",
+                    &example_org,
+                )
+                .await
+                .unwrap();
+
+            let api_example_org = ctx
+                .a_record(
+                    "kubizone-zone-deletion",
+                    "api-example-org",
+                    "api.example.org.",
+                )
+                .await
+                .unwrap();
+
+            ctx.wait_for(&sub_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&www_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.wait_for(&api_example_org, &[has_fqdn(), has_parent(&example_org)])
+                .await
+                .unwrap();
+
+            ctx.delete(&example_org).await.unwrap();
+
+            // Children referring to the zone lose their fqdn along with their parent.
+            ctx.wait_for(
+                &sub_example_org,
+                &[not(has_fqdn()), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            ctx.wait_for(
+                &www_example_org,
+                &[not(has_fqdn()), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+
+            // Children with a fully qualified domain name keep it.
+            ctx.wait_for(
+                &api_example_org,
+                &[has_fqdn(), not(has_parent(&example_org))],
+            )
+            .await
+            .unwrap();
+        })
+        .await;
+    }
+}